@@ -0,0 +1,140 @@
+use serenity::model::id::GuildId;
+use std::fs::{self, File};
+use std::io::{Error as IoError, ErrorKind, Read};
+use std::path::PathBuf;
+use super::{BlackCard, BlackCardId, WhiteCard, WhiteCardId, deck};
+use super::search::words;
+
+// cards a guild never wants to see, kept in guilds/<id>/bans.toml
+#[derive(Serialize, Deserialize, Default, Clone, Debug)]
+#[serde(default)]
+pub struct BanList {
+    // deck:number
+    pub white: Vec<String>,
+    pub black: Vec<String>,
+    // whole words, or runs of whole words, with * standing in for any letters
+    pub words: Vec<String>,
+}
+
+fn path(guild: GuildId) -> PathBuf {
+    deck::guild_root(guild).join("bans.toml")
+}
+
+impl BanList {
+    pub fn load(guild: GuildId) -> Result<BanList, IoError> {
+        let path = path(guild);
+        if !path.exists() {
+            return Ok(BanList::default());
+        }
+        let mut string = String::new();
+        File::open(&path)?.read_to_string(&mut string)?;
+        toml::from_str(&string).map_err(|x| IoError::new(ErrorKind::InvalidData, format!("{}: {}", path.display(), x)))
+    }
+    pub fn save(&self, guild: GuildId) -> Result<(), IoError> {
+        let string = toml::to_string(self).map_err(|x| IoError::new(ErrorKind::InvalidData, x.to_string()))?;
+        fs::create_dir_all(deck::guild_root(guild))?;
+        fs::write(path(guild), string)
+    }
+    pub fn is_empty(&self) -> bool {
+        self.white.is_empty() && self.black.is_empty() && self.words.is_empty()
+    }
+    pub fn bans_white(&self, card: &WhiteCard) -> bool {
+        self.white.iter().any(|x| x.parse::<WhiteCardId>().ok() == Some(card.id)) || self.bans_text(&card.message)
+    }
+    pub fn bans_black(&self, card: &BlackCard) -> bool {
+        self.black.iter().any(|x| x.parse::<BlackCardId>().ok() == Some(card.id)) || self.bans_text(&card.message)
+    }
+    fn bans_text(&self, message: &str) -> bool {
+        if self.words.is_empty() {
+            return false;
+        }
+        let message = words(message).collect::<Vec<_>>();
+        self.words.iter().any(|pattern| {
+            let pattern = words_with_wildcards(pattern);
+            !pattern.is_empty() && message.windows(pattern.len()).any(|window| window.iter().zip(pattern.iter()).all(|(word, pattern)| glob(pattern, word)))
+        })
+    }
+}
+
+// like search::words, but keeping the *s
+fn words_with_wildcards(pattern: &str) -> Vec<String> {
+    pattern.split(|c: char| !c.is_alphanumeric() && c != '*')
+        .filter(|x| !x.is_empty())
+        .map(|x| x.to_lowercase())
+        .collect()
+}
+
+pub fn is_valid_pattern(pattern: &str) -> bool {
+    words_with_wildcards(pattern).iter().any(|x| x.chars().any(|c: char| c != '*'))
+}
+
+// `*` matches any run of characters, everything else has to match exactly
+fn glob(pattern: &str, word: &str) -> bool {
+    match pattern.find('*') {
+        None => pattern == word,
+        Some(idx) => {
+            let (prefix, rest) = (&pattern[..idx], &pattern[idx + 1..]);
+            word.starts_with(prefix) && word.char_indices()
+                .map(|(x, _)| x)
+                .chain(Some(word.len()))
+                .filter(|x| *x >= prefix.len())
+                .any(|x| glob(rest, &word[x..]))
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn banning(words: &[&str]) -> BanList {
+        BanList { words: words.iter().map(|x| x.to_string()).collect(), ..BanList::default() }
+    }
+
+    #[test]
+    fn glob_without_stars_matches_exactly() {
+        assert!(glob("cat", "cat"));
+        assert!(!glob("cat", "cats"));
+        assert!(!glob("cat", "ca"));
+    }
+
+    #[test]
+    fn glob_stars_match_any_run() {
+        assert!(glob("cat*", "cat"));
+        assert!(glob("cat*", "catapult"));
+        assert!(glob("*cat", "bobcat"));
+        assert!(!glob("*cat", "cats"));
+        assert!(glob("c*t", "cat"));
+        assert!(glob("c*t", "ct"));
+    }
+
+    #[test]
+    fn glob_handles_several_stars() {
+        assert!(glob("*a*a*", "banana"));
+        assert!(glob("b*n*a", "banana"));
+        assert!(!glob("b*x*a", "banana"));
+        assert!(glob("**", ""));
+        assert!(glob("é*", "école"));
+    }
+
+    #[test]
+    fn words_are_banned_whole() {
+        assert!(banning(&["cat"]).bans_text("A cat, probably."));
+        assert!(!banning(&["cat"]).bans_text("Concatenation."));
+        assert!(banning(&["CAT"]).bans_text("cat"));
+    }
+
+    #[test]
+    fn runs_of_words_have_to_be_together() {
+        let bans = banning(&["hot dog*"]);
+        assert!(bans.bans_text("Eating hot dogs."));
+        assert!(!bans.bans_text("A hot day for a dog."));
+    }
+
+    #[test]
+    fn patterns_without_letters_ban_nothing() {
+        assert!(!banning(&["!!!"]).bans_text("Anything at all!!!"));
+        assert!(!is_valid_pattern("***"));
+        assert!(is_valid_pattern("dog*"));
+    }
+}
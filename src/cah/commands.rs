@@ -1,26 +1,75 @@
+use serenity::CACHE;
 use serenity::client::Context;
 use serenity::framework::standard::Args;
 use super::super::{CommandResult, get_name, get_name_other};
 use serenity::framework::standard::CommandError;
-use serenity::model::channel::Message;
+use serenity::model::channel::{Message, Reaction, ReactionType};
 use serenity::model::guild::Member;
-use super::{CahManager, State, format_card};
+use serenity::model::id::{ChannelId, GuildId, UserId};
+use super::bans::{self, BanList};
+use super::deck::{self, ContentRating};
+use super::display::{self, CardMessage};
+use super::export::{self, ExportFormat};
+use super::search::{self, CardIndex};
+use super::{BlackCard, WhiteCard, DeckKey, SfwPolicy, TagFilter};
+use super::{CahManager, State, HouseRule, GameMode, Judging, BlackCardId, format_card, haiku_card};
 use smallvec::SmallVec;
+use std::cmp;
+use std::collections::HashSet;
 use std::fmt::Write;
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use super::WhiteCardId;
 
+// awarded to the czar's first, second and third picks under Serious Business
+const SERIOUS_BUSINESS_POINTS: [u32; 3] = [3, 2, 1];
+const MAX_WRITE_IN_LENGTH: usize = 200;
+const MAX_UPLOAD_SIZE: u64 = 1024 * 1024;
+
 pub fn new_game(c: &mut Context, m: &Message, _a: Args) -> CommandResult {
     let mut data = c.data.lock();
     let manager = data.get_mut::<CahManager>().ok_or_else(|| CommandError("Couldn't load the CahManager".to_string()))?;
-    if !manager.new_game() {
+    if manager.is_running() {
         return Err(CommandError("A game is already running!".to_string()));
     }
+    if let Some(guild) = m.guild_id {
+        manager.set_bans(BanList::load(guild)?);
+    }
+    let mut excluded = HashSet::new();
+    let other = non_family_decks(m.channel_id, manager.get_deck_names(), m.guild_id)?;
+    if !other.is_empty() {
+        let family = manager.get_deck_names().iter().filter(|x| !other.contains(x)).cloned().collect::<Vec<_>>();
+        if manager.get_sfw_policy() == SfwPolicy::Refuse || family.is_empty() {
+            return Err(CommandError(format!("This channel isn't marked NSFW, so only family decks can be played here, and {} {}n't. Start the game in an NSFW channel or switch to family decks with `cah set-decks`.",
+                other.join(", "), if other.len() == 1 { "is" } else { "are" })));
+        }
+        m.channel_id.say(format!("This channel isn't marked NSFW, so only the family decks are in play: {}", family.join(", ")))?;
+        excluded = other.iter().map(|x| DeckKey::of(x)).collect();
+    }
+    // just for this game, the next one gets all the decks back if it's somewhere they're allowed
+    manager.set_excluded_decks(excluded);
+    manager.set_guild(m.guild_id);
+    manager.new_game();
     m.channel_id.say("Started CAH game.")?;
     manager.set_primary_channel(m.channel_id);
     c.set_game("Cards Against Humanity");
     Ok(())
 }
 
+// the decks that can't be played in the channel, which is all the non-family ones unless it's marked NSFW
+fn non_family_decks(channel: ChannelId, decks: &[String], guild: Option<GuildId>) -> Result<Vec<String>, CommandError> {
+    if channel.to_channel().map(|x| x.is_nsfw()).unwrap_or(false) {
+        return Ok(Vec::new());
+    }
+    let mut other = Vec::new();
+    for name in decks {
+        if deck::load_manifest(name, guild)?.rating != ContentRating::Family {
+            other.push(name.clone());
+        }
+    }
+    Ok(other)
+}
+
 pub fn my_cards(c: &mut Context, m: &Message, _a: Args) -> CommandResult {
     let mut data = c.data.lock();
     let manager = data.get_mut::<CahManager>().ok_or_else(|| CommandError("Couldn't load the CahManager".to_string()))?;
@@ -28,20 +77,40 @@ pub fn my_cards(c: &mut Context, m: &Message, _a: Args) -> CommandResult {
         return Err(CommandError("A game is not running!".to_string()));
     }
     let dm = m.author.create_dm_channel()?;
-    if manager.get_players().contains(m.author.id) {
-        let message = manager.get_hand(m.author.id)
-            .to_vec()
-            .iter()
-            .enumerate()
-            .map(|(idx, card)| idx.to_string() + ": " + &manager.resolve_white_card(*card, m.author.id).message + "\n")
-            .collect::<String>();
-        dm.say("Your cards: \n\n".to_string() + &message[..message.len() - 1])?;
+    if manager.get_mode() == GameMode::FreeText {
+        dm.say("There are no cards this game. Answer each black card with `cah answer <text>`.")?;
+    } else if manager.get_players().contains(m.author.id) {
+        let items = hand_items(manager, m.author.id);
+        send_hand(manager, dm.id, items)?;
     } else {
         dm.say("You are not in the game. Would you like to join? (Type `cah join` to join)".to_string())?;
     }
     Ok(())
 }
 
+fn hand_items(manager: &mut CahManager, user: UserId) -> Vec<String> {
+    manager.get_hand(user)
+        .to_vec()
+        .iter()
+        .map(|card| match manager.resolve_white_card(*card, user).map(|x| display::escape(&x.message)) {
+            Some(message) => message + removed_flag(manager, *card),
+            None => "(gone, and there are no cards left to replace it)".to_string(),
+        })
+        .collect()
+}
+
+fn send_hand(manager: &CahManager, channel: ChannelId, items: Vec<String>) -> CommandResult {
+    let mut message = CardMessage::new(display::white_card_colour(), "Your cards:".to_string());
+    message.items = items;
+    message.send(channel, manager.is_plain_text())?;
+    Ok(())
+}
+
+// for cards in hand from a deck that's been taken out with cah remove-deck
+fn removed_flag(manager: &CahManager, card: WhiteCardId) -> &'static str {
+    if manager.is_deck_removed(card.deck()) { " (removed deck)" } else { "" }
+}
+
 pub fn join_game(c: &mut Context, m: &Message, a: Args) -> CommandResult {
     let mut data = c.data.lock();
     let manager = data.get_mut::<CahManager>().ok_or_else(|| CommandError("Couldn't load the CahManager".to_string()))?;
@@ -49,17 +118,16 @@ pub fn join_game(c: &mut Context, m: &Message, a: Args) -> CommandResult {
         return Err(CommandError("A game is not running!".to_string()));
     }
     let dm = m.author.create_dm_channel()?;
-    if !manager.get_players().contains(m.author.id) {
-        let message = manager.get_hand(m.author.id)
-            .to_vec()
-            .iter()
-            .enumerate()
-            .map(|(idx, card)| idx.to_string() + ": " + &manager.resolve_white_card(*card, m.author.id).message + "\n")
-            .collect::<String>();
-        dm.say("Your cards: \n\n".to_string() + &message[..message.len() - 1])?;
-    } else {
+    if manager.get_players().contains(m.author.id) {
         dm.say("You are already in the game.".to_string())?;
+    } else if manager.get_mode() == GameMode::FreeText {
+        manager.get_hand(m.author.id);
+        dm.say("You've joined the game. Answer each black card with `cah answer <text>`.")?;
+    } else {
+        let items = hand_items(manager, m.author.id);
+        send_hand(manager, dm.id, items)?;
     }
+    update_status(m, manager);
     Ok(())
 }
 
@@ -67,11 +135,12 @@ pub fn set_decks(c: &mut Context, m: &Message, mut a: Args) -> CommandResult {
     let mut data = c.data.lock();
     let manager = data.get_mut::<CahManager>().ok_or_else(|| CommandError("Couldn't load the CahManager".to_string()))?;
     let mut force = false;
-    let mut no_base = false;
+    let mut no_deps = false;
     loop {
         match a.current().ok_or_else(|| CommandError("Not enough arguments".to_string()))? {
             "+force" => force = true,
-            "+no-base" => no_base = true,
+            // +no-base is from before decks could declare their own dependencies
+            "+no-deps" | "+no-base" => no_deps = true,
             _ => break,
         }
         a.next();
@@ -82,32 +151,546 @@ pub fn set_decks(c: &mut Context, m: &Message, mut a: Args) -> CommandResult {
     if manager.get_state() == State::Playing || manager.get_state() == State::Reading {
         return Err(CommandError("You can't change the decks while a black card is in play.".to_string()));
     }
-    let mut decks = a.iter().map(|x| x.unwrap()).collect::<Vec<String>>();
-    let base = "base".to_string();
-    if !no_base && !decks.contains(&base) {
-        decks.push(base)
+    // deck:weight sets how much of the piles the deck makes up, relative to the other weighted decks
+    let mut decks = Vec::new();
+    let mut weights = Vec::new();
+    let mut tags = TagFilter::default();
+    for arg in a.iter::<String>().map(|x| x.unwrap()) {
+        // +tag:x only plays cards tagged x (or any of the other included tags), -tag:x leaves them out
+        if arg.starts_with("+tag:") || arg.starts_with("-tag:") {
+            let tag = arg["+tag:".len()..].to_lowercase();
+            if !TagFilter::is_valid_tag(&tag) {
+                return Err(CommandError(format!("'{}' isn't a tag. Tags can only have letters, numbers, - and _.", display::escape(&arg))));
+            }
+            if arg.starts_with('+') {
+                tags.include.push(tag);
+            } else {
+                tags.exclude.push(tag);
+            }
+            continue;
+        }
+        match arg.rfind(':') {
+            Some(idx) => {
+                let weight = arg[idx + 1..].parse::<u32>().ok().filter(|x| *x > 0)
+                    .ok_or_else(|| CommandError(format!("'{}' isn't a deck weight (expected deck:number, e.g. base:50)", arg)))?;
+                decks.push(arg[..idx].to_string());
+                weights.push((arg[..idx].to_string(), weight));
+            },
+            None => decks.push(arg),
+        }
+    }
+    if !no_deps {
+        decks = deck::resolve_dependencies(&decks, m.guild_id)?;
+    }
+    // a deck without a weight would be played whole next to the cut down ones, so it's all or none
+    if !weights.is_empty() {
+        let unweighted = decks.iter().filter(|x| !weights.iter().any(|(name, _)| name == *x)).cloned().collect::<Vec<_>>();
+        if !unweighted.is_empty() {
+            return Err(CommandError(format!("Once one deck has a weight they all need one, including dependencies. Missing: {}", unweighted.join(", "))));
+        }
+    }
+    let (black_deck, white_deck) = load_decks(&decks, m.guild_id)?;
+    let bans = match m.guild_id {
+        Some(guild) => BanList::load(guild)?,
+        None => BanList::default(),
+    };
+    // a running game is held to the same rating rules as new_game, in the channel it was started in
+    let mut excluded = HashSet::new();
+    if manager.is_running() {
+        let other = non_family_decks(manager.get_primary_channel(), &decks, m.guild_id)?;
+        if !other.is_empty() {
+            if manager.get_sfw_policy() == SfwPolicy::Refuse || other.len() == decks.len() {
+                return Err(CommandError(format!("The game's channel isn't marked NSFW, so only family decks can be played in it, and {} {}n't.",
+                    other.join(", "), if other.len() == 1 { "is" } else { "are" })));
+            }
+            excluded = other.iter().map(|x| DeckKey::of(x)).collect::<HashSet<_>>();
+        }
+    }
+    // blanks aren't in the decks yet, but they'd always make it into the white pile
+    if !black_deck.iter().any(|x| !excluded.contains(&x.id.deck()) && super::black_allowed(x, &tags, &bans)) {
+        return Err(CommandError("That leaves no black cards to play with. Check the tags and bans.".to_string()));
+    }
+    if manager.get_blank_cards() == 0 && !white_deck.iter().any(|x| !excluded.contains(&x.id.deck()) && super::white_allowed(x, &tags, &bans)) {
+        return Err(CommandError("That leaves no white cards to play with. Check the tags and bans.".to_string()));
+    }
+    if m.guild_id.is_some() {
+        manager.set_bans(bans);
+    }
+    let mut string = format!("The decks have changed to: {:?}", decks);
+    if !weights.is_empty() {
+        let weighted = weights.iter().map(|&(ref name, weight)| format!("{}:{}", name, weight)).collect::<Vec<_>>();
+        write!(&mut string, ", weighted {}", weighted.join(" "))?;
+    }
+    if !tags.is_empty() {
+        write!(&mut string, ", with {}", tags)?;
+    }
+    if !excluded.is_empty() {
+        let family = decks.iter().filter(|x| !excluded.contains(&DeckKey::of(x))).cloned().collect::<Vec<_>>();
+        write!(&mut string, ". This channel isn't marked NSFW, so only the family decks are in play: {}", family.join(", "))?;
+    }
+    display::say(manager.get_primary_channel(), &string)?;
+    manager.set_tag_filter(tags);
+    manager.set_deck_weights(weights.into_iter().map(|(name, weight)| (DeckKey::of(&name), weight)).collect());
+    if manager.is_running() {
+        manager.set_excluded_decks(excluded);
+    }
+    manager.set_decks(black_deck, white_deck, decks);
+    Ok(())
+}
+
+// unlike set-decks, these work mid-game and leave the cards people already have alone
+pub fn add_deck(c: &mut Context, m: &Message, a: Args) -> CommandResult {
+    let mut data = c.data.lock();
+    let manager = data.get_mut::<CahManager>().ok_or_else(|| CommandError("Couldn't load the CahManager".to_string()))?;
+    let decks = a.multiple::<String>().map_err(|_| CommandError("Usage: `cah add-deck <deck>...`".to_string()))?;
+    if !manager.get_deck_weights().is_empty() {
+        return Err(CommandError("The decks are weighted, so new ones need a weight too. Use `cah set-decks` between rounds instead.".to_string()));
+    }
+    let added = deck::resolve_dependencies(&decks, m.guild_id)?
+        .into_iter()
+        .filter(|x| !manager.get_deck_names().contains(x))
+        .collect::<Vec<_>>();
+    if added.is_empty() {
+        return Err(CommandError("Those decks are already in play.".to_string()));
+    }
+    // outside a game, new_game checks them against wherever it's started
+    if manager.is_running() {
+        let other = non_family_decks(manager.get_primary_channel(), &added, m.guild_id)?;
+        if !other.is_empty() {
+            return Err(CommandError(format!("The game's channel isn't marked NSFW, so only family decks can be played in it, and {} {}n't.",
+                other.join(", "), if other.len() == 1 { "is" } else { "are" })));
+        }
+    }
+    // load them all first so a bad deck doesn't leave the others half added
+    let mut loaded = Vec::new();
+    for name in added.iter() {
+        loaded.push(super::load_deck(name, m.guild_id)?);
+    }
+    for ((black, white), name) in loaded.into_iter().zip(added.iter()) {
+        manager.add_deck(black, white, name.clone());
+    }
+    manager.get_primary_channel().say(format!("Shuffled {} into the piles.", added.join(", ")))?;
+    Ok(())
+}
+
+pub fn remove_deck(c: &mut Context, m: &Message, a: Args) -> CommandResult {
+    let mut data = c.data.lock();
+    let manager = data.get_mut::<CahManager>().ok_or_else(|| CommandError("Couldn't load the CahManager".to_string()))?;
+    let decks = a.multiple::<String>().map_err(|_| CommandError("Usage: `cah remove-deck <deck>...`".to_string()))?;
+    for name in decks.iter() {
+        if !manager.get_deck_names().contains(name) {
+            return Err(CommandError(format!("{} isn't in play.", name)));
+        }
+    }
+    if decks.len() >= manager.get_deck_names().len() {
+        return Err(CommandError("That would leave no decks at all.".to_string()));
+    }
+    // a deck can't go while something still in play depends on it
+    for name in manager.get_deck_names().iter().filter(|x| !decks.contains(x)) {
+        let needed = deck::load_manifest(name, m.guild_id)?.dependencies
+            .into_iter()
+            .filter(|x| decks.contains(x))
+            .collect::<Vec<_>>();
+        if !needed.is_empty() {
+            return Err(CommandError(format!("{} needs {}, so take it out too or leave {} in.", name, needed.join(", "), if needed.len() == 1 { "it" } else { "them" })));
+        }
+    }
+    for name in decks.iter() {
+        manager.remove_deck(name);
     }
+    manager.get_primary_channel().say(format!("Took {} out of the piles. Cards from {} that are already in hands can still be played this game.",
+        decks.join(", "), if decks.len() == 1 { "it" } else { "them" }))?;
+    Ok(())
+}
+
+fn load_decks(decks: &[String], guild: Option<GuildId>) -> Result<(Vec<BlackCard>, Vec<WhiteCard>), CommandError> {
     let mut black_deck = Vec::new();
     let mut white_deck = Vec::new();
     for deck in decks.iter() {
-        let (mut black, mut white) = super::load_deck(deck)?;
+        let (mut black, mut white) = super::load_deck(deck, guild)?;
         black_deck.append(&mut black);
         white_deck.append(&mut white);
     }
-    manager.get_primary_channel().say(format!("The decks have changed to: {:?}", decks))?;
-    manager.set_decks(black_deck, white_deck, decks);
-    Ok(())
+    Ok((black_deck, white_deck))
 }
 
-pub fn get_decks(c: &mut Context, m: &Message, _a: Args) -> CommandResult {
+pub fn get_decks(c: &mut Context, m: &Message, mut a: Args) -> CommandResult {
     let data = c.data.lock();
     let manager = data.get::<CahManager>().ok_or_else(|| CommandError("Couldn't load the CahManager".to_string()))?;
-    let mut string = "Loaded decks:\n\n".to_string();
-    for deck in manager.get_deck_names() {
-        string.push_str(deck);
-        string.push_str("\n");
+    let mut string = String::new();
+    if let Ok(name) = a.single::<String>() {
+        // the full details for one deck
+        let manifest = deck::load_manifest(&name, m.guild_id)?;
+        writeln!(&mut string, "**{}** ({})", display::escape(manifest.name.as_ref().unwrap_or(&name)), display::escape(&name))?;
+        if let Some(ref description) = manifest.description {
+            writeln!(&mut string, "{}", display::escape(description))?;
+        }
+        writeln!(&mut string, "Rating: {}", manifest.rating)?;
+        if let Some(ref language) = manifest.language {
+            writeln!(&mut string, "Language: {}", display::escape(language))?;
+        }
+        if let Some(ref author) = manifest.author {
+            writeln!(&mut string, "Author: {}", display::escape(author))?;
+        }
+        if let Some(ref license) = manifest.license {
+            writeln!(&mut string, "License: {}", display::escape(license))?;
+        }
+        if let Some(ref attribution) = manifest.attribution {
+            writeln!(&mut string, "{}", display::escape(attribution))?;
+        }
+        if !manifest.tags.is_empty() {
+            writeln!(&mut string, "Tags: {}", display::escape(&manifest.tags.join(", ")))?;
+        }
+        if !manifest.dependencies.is_empty() {
+            writeln!(&mut string, "Requires: {}", manifest.dependencies.join(", "))?;
+        }
+    } else {
+        string.push_str("Available decks (* = loaded, `cah decks <name>` for details):\n\n");
+        let custom = match m.guild_id {
+            Some(guild) => deck::guild_decks(guild)?,
+            None => Vec::new(),
+        };
+        for name in deck::available_decks(m.guild_id)? {
+            let manifest = deck::load_manifest(&name, m.guild_id)?;
+            let loaded = if manager.get_deck_names().contains(&name) { "* " } else { "" };
+            write!(&mut string, "{}{}", loaded, name)?;
+            if let Some(ref display_name) = manifest.name {
+                write!(&mut string, " - {}", display::escape(display_name))?;
+            }
+            if custom.contains(&name) {
+                write!(&mut string, " [custom]")?;
+            }
+            write!(&mut string, " ({})", manifest.rating)?;
+            if !manifest.dependencies.is_empty() {
+                write!(&mut string, ", requires {}", manifest.dependencies.join(", "))?;
+            }
+            string.push_str("\n");
+        }
     }
-    m.channel_id.say(string)?;
+    display::say(m.channel_id, &string)?;
+    Ok(())
+}
+
+pub fn check_decks(c: &mut Context, m: &Message, a: Args) -> CommandResult {
+    let mut decks = a.multiple::<String>().unwrap_or_default();
+    if decks.is_empty() {
+        decks = deck::available_decks(m.guild_id)?;
+    }
+    let problems = deck::check_decks(&decks, m.guild_id);
+    let mut string = format!("Checked {} decks, found {} problems", decks.len(), problems.len());
+    for problem in problems.iter() {
+        write!(&mut string, "\n{}", problem)?;
+    }
+    display::say(m.channel_id, &string)?;
+    Ok(())
+}
+
+// cah deck upload <name>, with the deck attached: white and black csvs (told apart by their columns), a json export, and/or a deck.toml
+pub fn upload_deck(c: &mut Context, m: &Message, mut a: Args) -> CommandResult {
+    let guild = m.guild_id.ok_or_else(|| CommandError("Decks can only be uploaded in a server.".to_string()))?;
+    let name = a.single::<String>().map_err(|_| CommandError("Usage: `cah deck upload <name>`, with the deck's files attached".to_string()))?;
+    if !deck::is_valid_deck_name(&name) {
+        return Err(CommandError("Deck names can only have letters, numbers, - and _.".to_string()));
+    }
+    if deck::is_builtin_deck(&name) {
+        return Err(CommandError(format!("There's already a built-in deck called {}.", name)));
+    }
+    if m.attachments.is_empty() {
+        return Err(CommandError("Attach the deck to the message: white.csv and/or black.csv, or a .json export, plus an optional deck.toml.".to_string()));
+    }
+    let mut files = Vec::new();
+    let mut problems = Vec::new();
+    for attachment in m.attachments.iter() {
+        if attachment.size > MAX_UPLOAD_SIZE {
+            return Err(CommandError(format!("{} is too big, deck files can be up to {}KB.", attachment.filename, MAX_UPLOAD_SIZE / 1024)));
+        }
+        let contents = attachment.download()?;
+        let kind = deck::DeckFile::identify(&attachment.filename, &contents)
+            .ok_or_else(|| CommandError(format!("I don't know what to do with {}. Decks are .csv, .json and .toml files.", attachment.filename)))?;
+        if files.iter().any(|&(x, _)| x == kind) {
+            return Err(CommandError(format!("There's more than one {} attached.", kind.file_name())));
+        }
+        problems.extend(deck::check_deck_file(kind, &attachment.filename, &contents, Some(guild)));
+        files.push((kind, contents));
+    }
+    let has_cards = files.iter().any(|&(x, _)| x != deck::DeckFile::Manifest);
+    if !has_cards && !deck::guild_decks(guild)?.contains(&name) {
+        return Err(CommandError(format!("There's no deck called {} yet, so there needs to be some cards attached.", name)));
+    }
+    if !problems.is_empty() {
+        let mut string = format!("Found {} problems, so the deck wasn't saved:", problems.len());
+        for problem in problems.iter() {
+            write!(&mut string, "\n{}", problem)?;
+        }
+        return Err(CommandError(string));
+    }
+    let (black, white) = {
+        // the same lock the deck edits hold
+        let _lock = c.data.lock();
+        deck::save_guild_deck(guild, &name, &files)?;
+        super::load_deck(&name, Some(guild))?
+    };
+    m.channel_id.say(format!("Saved deck {} with {} black and {} white cards. Use `cah set-decks {}` to play with it.", name, black.len(), white.len(), name))?;
+    Ok(())
+}
+
+pub fn create_deck(c: &mut Context, m: &Message, mut a: Args) -> CommandResult {
+    let guild = m.guild_id.ok_or_else(|| CommandError("Decks can only be made in a server.".to_string()))?;
+    let name = a.single::<String>().map_err(|_| CommandError("Usage: `cah deck create <name>`".to_string()))?;
+    if !deck::is_valid_deck_name(&name) {
+        return Err(CommandError("Deck names can only have letters, numbers, - and _.".to_string()));
+    }
+    if deck::is_builtin_deck(&name) || deck::guild_decks(guild)?.contains(&name) {
+        return Err(CommandError(format!("There's already a deck called {}.", name)));
+    }
+    deck::save_guild_deck(guild, &name, &[])?;
+    m.channel_id.say(format!("Created deck {0}. Add cards with `cah deck add-white {0} <text>` and `cah deck add-black {0} [pick:N] <text>`, with ____ for each blank.", name))?;
+    Ok(())
+}
+
+// everything after the deck name, without any quotes around it
+fn card_text(a: &Args) -> Result<String, CommandError> {
+    let text = a.rest().trim().trim_matches('"').trim();
+    if text.is_empty() {
+        Err(CommandError("The card needs some text.".to_string()))
+    } else if text.chars().count() > MAX_WRITE_IN_LENGTH {
+        Err(CommandError(format!("Cards can be at most {} characters long.", MAX_WRITE_IN_LENGTH)))
+    } else {
+        Ok(text.to_string())
+    }
+}
+
+pub fn add_white_card(c: &mut Context, m: &Message, mut a: Args) -> CommandResult {
+    let guild = m.guild_id.ok_or_else(|| CommandError("Decks can only be edited in a server.".to_string()))?;
+    let name = a.single::<String>().map_err(|_| CommandError("Usage: `cah deck add-white <deck> <text>`".to_string()))?;
+    let text = card_text(&a)?;
+    // held until the deck's saved, so two edits at once can't undo each other
+    let _lock = c.data.lock();
+    let mut editor = deck::DeckEditor::open(guild, &name).map_err(|x| CommandError(x.to_string()))?;
+    let id = editor.add_white(text).map_err(|x| CommandError(x.to_string()))?;
+    editor.save()?;
+    m.channel_id.say(format!("Added white card {}:{}. It'll be in play the next time the decks are set.", name, id))?;
+    Ok(())
+}
+
+pub fn add_black_card(c: &mut Context, m: &Message, mut a: Args) -> CommandResult {
+    let guild = m.guild_id.ok_or_else(|| CommandError("Decks can only be edited in a server.".to_string()))?;
+    let usage = || CommandError("Usage: `cah deck add-black <deck> [pick:N] <text>`".to_string());
+    let name = a.single::<String>().map_err(|_| usage())?;
+    // a flag rather than a bare number, so cards can start with one
+    let mut pick = None;
+    if let Some(flag) = a.current().filter(|x| x.starts_with("pick:")).map(|x| x["pick:".len()..].to_string()) {
+        pick = Some(flag.parse::<u8>().ok().filter(|x| *x > 0).ok_or_else(|| CommandError("Pick has to be a number from 1 up.".to_string()))?);
+        a.next();
+    }
+    let text = card_text(&a)?;
+    // without a pick it's however many blanks there are, and pick 3 cards get the usual draw 2
+    let play = pick.unwrap_or_else(|| cmp::max(1, super::count_blanks(&text) as u8));
+    let draw = if play >= 3 { play - 1 } else { 0 };
+    let _lock = c.data.lock();
+    let mut editor = deck::DeckEditor::open(guild, &name).map_err(|x| CommandError(x.to_string()))?;
+    let id = editor.add_black(text, play, draw).map_err(|x| CommandError(x.to_string()))?;
+    editor.save()?;
+    m.channel_id.say(format!("Added black card {}:{} (pick {}). It'll be in play the next time the decks are set.", name, id, play))?;
+    Ok(())
+}
+
+pub fn remove_card(c: &mut Context, m: &Message, mut a: Args) -> CommandResult {
+    let guild = m.guild_id.ok_or_else(|| CommandError("Decks can only be edited in a server.".to_string()))?;
+    let usage = || CommandError("Usage: `cah deck remove <deck> <id> [white|black]`".to_string());
+    let name = a.single::<String>().map_err(|_| usage())?;
+    let id = a.single::<u64>().map_err(|_| usage())?;
+    let black = match a.single::<String>().ok().as_ref().map(|x| &x[..]) {
+        Some("black") => Some(true),
+        Some("white") => Some(false),
+        Some(_) => return Err(usage()),
+        None => None,
+    };
+    let _lock = c.data.lock();
+    let mut editor = deck::DeckEditor::open(guild, &name).map_err(|x| CommandError(x.to_string()))?;
+    if black.is_none() && editor.has_black(id) && editor.has_white(id) {
+        return Err(CommandError(format!("There's a white and a black card numbered {}, say which one to remove.", id)));
+    }
+    if editor.remove(id, black) == 0 {
+        return Err(CommandError(format!("There's no card {}:{}.", name, id)));
+    }
+    editor.save()?;
+    m.channel_id.say(format!("Removed card {}:{}.", name, id))?;
+    Ok(())
+}
+
+// cah deck list for the server's own decks, cah deck list <name> for the cards in one
+pub fn list_deck(c: &mut Context, m: &Message, mut a: Args) -> CommandResult {
+    let guild = m.guild_id.ok_or_else(|| CommandError("Only servers have their own decks.".to_string()))?;
+    let mut string = String::new();
+    if let Ok(name) = a.single::<String>() {
+        let (black, white) = super::load_deck(&name, Some(guild)).map_err(|x| CommandError(x.to_string()))?;
+        writeln!(&mut string, "**{}**: {} black and {} white cards", name, black.len(), white.len())?;
+        for card in black.iter() {
+            writeln!(&mut string, "black {}: {} (pick {})", card.id.number(), display::escape(&card.message), card.play)?;
+        }
+        for card in white.iter() {
+            writeln!(&mut string, "white {}: {}", card.id.number(), display::escape(&card.message))?;
+        }
+    } else {
+        let decks = deck::guild_decks(guild)?;
+        if decks.is_empty() {
+            string.push_str("This server doesn't have any decks of its own yet. Make one with `cah deck create <name>` or `cah deck upload <name>`.");
+        } else {
+            write!(&mut string, "This server's decks: {}", decks.join(", "))?;
+        }
+    }
+    display::say(m.channel_id, &string)?;
+    Ok(())
+}
+
+// cah export <deck> <csv|json|html>
+pub fn export_deck(c: &mut Context, m: &Message, mut a: Args) -> CommandResult {
+    let usage = || CommandError("Usage: `cah export <deck> <csv|json|html>`".to_string());
+    let name = a.single::<String>().map_err(|_| usage())?;
+    let format = a.single::<String>().map_err(|_| usage())?.parse::<ExportFormat>().map_err(CommandError)?;
+    let files = export::export_deck(&name, m.guild_id, format).map_err(|x| CommandError(x.to_string()))?;
+    let attachments = files.iter().map(|&(ref file_name, ref contents)| (&contents[..], &file_name[..])).collect::<Vec<_>>();
+    m.channel_id.send_files(attachments, |x| x.content(format!("Here's {}.", display::escape(&name))))?;
+    Ok(())
+}
+
+// cah search [+page:N] <text>
+pub fn search_cards(c: &mut Context, m: &Message, mut a: Args) -> CommandResult {
+    let mut page = 1;
+    if let Some(flag) = a.current().filter(|x| x.starts_with("+page:")).map(|x| x["+page:".len()..].to_string()) {
+        page = flag.parse::<usize>().ok().filter(|x| *x > 0).ok_or_else(|| CommandError("Pages start at 1.".to_string()))?;
+        a.next();
+    }
+    let query = a.rest().trim().to_string();
+    if query.is_empty() {
+        return Err(CommandError("Usage: `cah search [+page:N] <text>`".to_string()));
+    }
+    let index = CardIndex::build(m.guild_id)?;
+    let found = index.search(&query);
+    if found.is_empty() {
+        m.channel_id.say(format!("No cards found for '{}'.", display::escape(&query)))?;
+        return Ok(());
+    }
+    let pages = (found.len() + search::PAGE_SIZE - 1) / search::PAGE_SIZE;
+    if page > pages {
+        return Err(CommandError(format!("There are only {} pages of results.", pages)));
+    }
+    let mut string = format!("Found {} cards for '{}' (page {} of {}):\n", found.len(), display::escape(&query), page, pages);
+    for card in found.iter().skip((page - 1) * search::PAGE_SIZE).take(search::PAGE_SIZE) {
+        write!(&mut string, "\n{}", card)?;
+    }
+    if page < pages {
+        write!(&mut string, "\n\n`cah search +page:{} {}` for more.", page + 1, display::escape_mentions(&query))?;
+    }
+    display::say(m.channel_id, &string)?;
+    Ok(())
+}
+
+pub fn show_card(c: &mut Context, m: &Message, mut a: Args) -> CommandResult {
+    let id = a.single::<String>().map_err(|_| CommandError("Usage: `cah card <deck:number>`".to_string()))?;
+    let cards = search::find_card(&id, m.guild_id).map_err(CommandError)?;
+    let string = cards.iter().map(|x| x.to_string()).collect::<Vec<_>>().join("\n");
+    display::say(m.channel_id, &string)?;
+    Ok(())
+}
+
+// cah ban <deck:number> [white|black], or cah ban <words> where * stands in for any letters
+pub fn ban(c: &mut Context, m: &Message, a: Args) -> CommandResult {
+    edit_bans(c, m, a, true)
+}
+
+pub fn unban(c: &mut Context, m: &Message, a: Args) -> CommandResult {
+    edit_bans(c, m, a, false)
+}
+
+fn edit_bans(c: &mut Context, m: &Message, mut a: Args, add: bool) -> CommandResult {
+    let guild = m.guild_id.ok_or_else(|| CommandError("Bans are per server.".to_string()))?;
+    let usage = || CommandError(format!("Usage: `cah {} <deck:number> [white|black]` or `cah {0} <words>`", if add { "ban" } else { "unban" }));
+    // held from the load to the save, so two bans at once can't undo each other
+    let mut data = c.data.lock();
+    let manager = data.get_mut::<CahManager>().ok_or_else(|| CommandError("Couldn't load the CahManager".to_string()))?;
+    let mut list = BanList::load(guild)?;
+    let changed;
+    let mut target = a.single_n::<String>().map_err(|_| usage())?;
+    if super::parse_card_id(&target).is_ok() {
+        a.next();
+        let colour = a.single::<String>().ok();
+        let (mut white, mut black) = match colour.as_ref().map(|x| &x[..]) {
+            Some("white") => (true, false),
+            Some("black") => (false, true),
+            Some(_) => return Err(usage()),
+            None => (true, true),
+        };
+        if add {
+            // only ban the colours that exist, so the list doesn't fill up with nothing
+            let cards = search::find_card(&target, Some(guild)).map_err(CommandError)?;
+            white &= cards.iter().any(|x| if let search::FoundCard::White(_) = x.card { true } else { false });
+            black &= cards.iter().any(|x| if let search::FoundCard::Black(_) = x.card { true } else { false });
+        }
+        changed = update_ban(&mut list.white, &target, white && add, white)
+            | update_ban(&mut list.black, &target, black && add, black);
+    } else {
+        let pattern = a.rest().trim().trim_matches('"').to_lowercase();
+        if !bans::is_valid_pattern(&pattern) {
+            return Err(usage());
+        }
+        changed = update_ban(&mut list.words, &pattern, add, true);
+        target = pattern;
+    }
+    if !changed {
+        return Err(CommandError(if add { "That's already banned." } else { "That isn't banned." }.to_string()));
+    }
+    if add && manager.get_guild() == Some(guild) {
+        let (black, white) = manager.pile_sizes(&list);
+        if black == 0 || white == 0 {
+            return Err(CommandError(format!("That would leave no {} cards to play with, so it wasn't banned.", if black == 0 { "black" } else { "white" })));
+        }
+    }
+    list.save(guild)?;
+    if manager.get_guild() == Some(guild) {
+        // new bans take the cards out of the piles right away; unbanned ones come back with the next game or deck change
+        manager.set_bans(list);
+    }
+    m.channel_id.say(format!("{} {}.", if add { "Banned" } else { "Unbanned" }, display::escape(&target)))?;
+    Ok(())
+}
+
+// adds or removes one entry, if `applies`. true if anything changed
+fn update_ban(list: &mut Vec<String>, entry: &str, add: bool, applies: bool) -> bool {
+    if !applies {
+        return false;
+    }
+    let present = list.iter().any(|x| x == entry);
+    if add && !present {
+        list.push(entry.to_string());
+        true
+    } else if !add && present {
+        list.retain(|x| x != entry);
+        true
+    } else {
+        false
+    }
+}
+
+pub fn list_bans(c: &mut Context, m: &Message, _a: Args) -> CommandResult {
+    let guild = m.guild_id.ok_or_else(|| CommandError("Bans are per server.".to_string()))?;
+    let list = BanList::load(guild)?;
+    if list.is_empty() {
+        m.channel_id.say("Nothing is banned here.")?;
+        return Ok(());
+    }
+    let mut string = String::from("Banned in this server:\n");
+    if !list.white.is_empty() {
+        write!(&mut string, "\nWhite cards: {}", display::escape(&list.white.join(", ")))?;
+    }
+    if !list.black.is_empty() {
+        write!(&mut string, "\nBlack cards: {}", display::escape(&list.black.join(", ")))?;
+    }
+    if !list.words.is_empty() {
+        write!(&mut string, "\nWords: {}", display::escape(&list.words.join(", ")))?;
+    }
+    display::say(m.channel_id, &string)?;
     Ok(())
 }
 
@@ -122,31 +705,135 @@ pub fn draw_black_card(c: &mut Context, m: &Message, _a: Args) -> CommandResult
         State::Playing => Err(CommandError("A black card is already in play".to_string())),
         State::Reading => Err(CommandError("Wait for the winner to be announced".to_string())),
         State::Waiting => {
-            let (draw, id) = {
-                let card = manager.draw_black();
-                m.channel_id.say(card.message.clone())?;
-                if card.draw > 0 || card.play > 1 {
-                    m.channel_id.say(format!("(Draw {}, play {})", card.draw, card.play))?;
-                }
-                (card.draw, card.id)
-            };
-            manager.current_black_card = Some(id);
-            // stupid borrow checker
-            let players = manager.get_players().all().iter().cloned().collect::<SmallVec<[_; 20]>>();
-            for player in players {
-                for _ in 0..draw {
-                    let card = manager.draw_white();
-                    manager.get_hand(player).push(card)
-                }
-                manager.set_state(State::Playing);
+            let id = manager.draw_black().map(|x| x.id)
+                .ok_or_else(|| CommandError("There are no black cards to draw. Check the decks, tags and bans with `cah set-decks`.".to_string()))?;
+            start_round(c, m, manager, m.channel_id, id)
+        }
+    }
+}
+
+// sends the card as an image with the text alongside, if images are turned on. false if they aren't or the card is too long to draw
+fn send_card_image(manager: &CahManager, channel: ChannelId, card: &BlackCard, fills: &[&WhiteCard], text: &str) -> Result<bool, CommandError> {
+    let png = match manager.get_renderer() {
+        Some(renderer) => renderer.render_black_card(card, fills)?,
+        None => None,
+    };
+    match png {
+        Some(png) => {
+            // the image goes with the first part of the text
+            let mut chunks = display::split_message(text).into_iter();
+            channel.send_files(vec![(&png[..], "card.png")], |x| x.content(chunks.next().unwrap_or_default()))?;
+            for chunk in chunks {
+                channel.say(chunk)?;
             }
-            Ok(())
+            Ok(true)
+        },
+        // too long to draw, it goes as text
+        None => Ok(false),
+    }
+}
+
+// the round number and scores, for under the black card and submissions
+fn round_footer(m: &Message, manager: &CahManager) -> String {
+    let mut scores = manager.get_scores().filter(|&(_, x)| x > 0).collect::<Vec<_>>();
+    scores.sort_by(|a, b| b.1.cmp(&a.1));
+    if scores.is_empty() {
+        format!("Round {}", manager.get_round())
+    } else {
+        let scores = scores.iter().map(|&(id, score)| format!("{} {}", get_name_other(m, id), score)).collect::<Vec<_>>();
+        format!("Round {} · {}", manager.get_round(), scores.join(", "))
+    }
+}
+
+// shows the black card and deals out any extra cards it calls for
+fn start_round(c: &Context, m: &Message, manager: &mut CahManager, channel: ChannelId, id: BlackCardId) -> CommandResult {
+    manager.next_round();
+    let footer = round_footer(m, manager);
+    let draw = {
+        let card = manager.get_black_card(id).ok_or_else(|| CommandError("Internal error: Drawn black card is not a defined card".to_string()))?;
+        let mut message = CardMessage::new(display::black_card_colour(), display::escape(&card.message));
+        message.footer = Some(footer);
+        let draw = if manager.get_mode() == GameMode::FreeText {
+            if card.play > 1 {
+                message.description = format!("(Write {} answers, e.g. `cah answer \"first\" \"second\"`)", card.play);
+            }
+            0
+        } else {
+            if card.draw > 0 || card.play > 1 {
+                message.description = format!("(Draw {}, play {})", card.draw, card.play);
+            }
+            card.draw
+        };
+        if !send_card_image(manager, channel, card, &[], &message.to_text())? {
+            message.send(channel, manager.is_plain_text())?;
+        }
+        draw
+    };
+    manager.current_black_card = Some(id);
+    // stupid borrow checker
+    let players = manager.get_players().all().iter().cloned().collect::<SmallVec<[_; 20]>>();
+    for player in players {
+        manager.draw_into_hand(player, draw as usize);
+    }
+    manager.set_state(State::Playing);
+    if let Some(deadline) = manager.get_round_deadline() {
+        start_timer(c, m, deadline);
+    }
+    // the round's already started, so like update_status, a board that doesn't send isn't worth failing over
+    let sent = match status_board(m, manager).send(manager.get_primary_channel(), manager.is_plain_text()) {
+        Ok(sent) => sent,
+        Err(err) => {
+            eprintln!("Error sending the status board: {}", err);
+            None
+        },
+    };
+    manager.set_status_message(sent.map(|x| x.id));
+    Ok(())
+}
+
+// reads out the answers when the round's time runs out, unless everyone's played by then
+fn start_timer(c: &Context, m: &Message, deadline: SystemTime) {
+    let (c, m) = (c.clone(), m.clone());
+    thread::spawn(move || {
+        if let Ok(wait) = deadline.duration_since(SystemTime::now()) {
+            thread::sleep(wait);
+        }
+        let mut data = c.data.lock();
+        let manager = match data.get_mut::<CahManager>() {
+            Some(manager) => manager,
+            None => return,
+        };
+        // the deadline's different for every round, so this is still the round the timer was started for
+        if manager.get_state() != State::Playing || manager.get_round_deadline() != Some(deadline) {
+            return;
+        }
+        if let Err(err) = time_up(&c, &m, manager) {
+            eprintln!("Error ending the round: {}", err.0);
         }
+    });
+}
+
+// goes ahead with whoever's played, or on to the next turn if nobody has
+fn time_up(c: &Context, m: &Message, manager: &mut CahManager) -> CommandResult {
+    let (played, waiting): (Vec<_>, Vec<_>) = manager.get_answering_players().into_iter()
+        .partition(|x| manager.get_cards_in_play(*x).map(|x| !x.is_empty()).unwrap_or(false));
+    let waiting = waiting.iter().map(|x| get_name_other(m, *x)).collect::<Vec<_>>();
+    if played.is_empty() {
+        manager.get_primary_channel().say("Time's up, and nobody played.")?;
+        manager.set_state(State::Waiting);
+        update_status(m, manager);
+        let next = manager.get_players_mut().next_player();
+        manager.get_primary_channel().say(format!("It's <@{}>'s turn.", next.0))?;
+        return Ok(());
     }
+    manager.get_primary_channel().say(format!("Time's up! Going ahead without {}.", waiting.join(", ")))?;
+    reveal(c, m, manager)?;
+    update_status(m, manager);
+    Ok(())
 }
 
 pub fn play_white_card(c: &mut Context, m: &Message, mut a: Args) -> CommandResult {
-    let indices = a.iter::<usize>().collect::<Result<SmallVec<[_; 5]>, _>>()?;
+    let (indices, texts) = parse_play(&mut a)?;
     let mut data = c.data.lock();
     let manager = data.get_mut::<CahManager>().ok_or_else(|| CommandError("Couldn't load the CahManager".to_string()))?;
     match manager.get_state() {
@@ -154,66 +841,221 @@ pub fn play_white_card(c: &mut Context, m: &Message, mut a: Args) -> CommandResu
         State::Reading => Err(CommandError("Wait for the next round".to_string())),
         State::Waiting => Err(CommandError("Wait for a black card to be chosen".to_string())),
         State::Playing => {
-            if m.author.id == manager.get_players().current() {
+            if manager.get_mode() == GameMode::FreeText {
+                return Err(CommandError("There are no cards this game - use `cah answer <text>` instead.".to_string()));
+            }
+            if !manager.is_answering(m.author.id) {
                 return Err(CommandError("You can't play a card - you're the Card Czar this round.".to_string()));
             }
-            let hand = manager.get_hand(m.author.id).iter().cloned().collect::<SmallVec<[_; 20]>>();
-            let card = manager.get_current_black_card().ok_or_else(|| CommandError("Internal error: Game is in State::Playing but there is no current black card".to_string()))?;
+            let selection = select_cards(manager, m.author.id, &indices, &texts)?;
             {
-                let card = manager.get_black_card(card).ok_or_else(|| CommandError("Internal error: Current black card is not a defined card".to_string()))?;
-                if (card.play as usize) != indices.len() {
-                    return Err(CommandError(format!("Wrong number of cards played (expected {}, got {})", card.play, indices.len())))
-                }
-                if let Some(x) = indices.iter().cloned().find(|x| hand.len() <= *x) {
-                    return Err(CommandError(format!("{} is not a card in your hand.", x)))
-                }
-                let invalid = indices.iter().filter(|x| manager.get_white_card(hand[**x]).is_none()).collect::<SmallVec<[_; 5]>>();
-                if invalid.len() != 0 {
-                    let mut string = String::with_capacity(95);
-                    string.push_str("Cards ");
-                    for n in &invalid[..invalid.len() - 1] {
-                        write!(&mut string, "{}, ", n)?;
-                    }
-                    write!(&mut string, "and {} are invalid (has the deck been reset?) Use `cah cards` to refresh.", invalid[invalid.len() - 1])?;
-                    return Err(CommandError(string))
-                }
+                let cards = manager.get_cards_in_play_mut(m.author.id);
+                cards.clear();
+                cards.extend(selection);
+            }
+            // the card's in either way, so a message that didn't send doesn't make the play look like it failed
+            if let Err(err) = reveal_if_done(c, m, manager) {
+                eprintln!("Error revealing the answers: {}", err.0);
+            }
+            update_status(m, manager);
+            Ok(())
+        },
+    }
+}
+
+pub fn gamble(c: &mut Context, m: &Message, mut a: Args) -> CommandResult {
+    let (indices, texts) = parse_play(&mut a)?;
+    let mut data = c.data.lock();
+    let manager = data.get_mut::<CahManager>().ok_or_else(|| CommandError("Couldn't load the CahManager".to_string()))?;
+    if !manager.has_rule(HouseRule::Gambling) {
+        return Err(CommandError("Gambling isn't allowed in this game (see `cah rules`)".to_string()));
+    }
+    match manager.get_state() {
+        State::Off => Err(CommandError("The game is not running".to_string())),
+        State::Reading => Err(CommandError("Too late - everyone has played.".to_string())),
+        State::Waiting => Err(CommandError("Wait for a black card to be chosen".to_string())),
+        State::Playing => {
+            if manager.get_mode() == GameMode::FreeText {
+                return Err(CommandError("There are no cards to gamble with this game.".to_string()));
+            }
+            if !manager.is_answering(m.author.id) {
+                return Err(CommandError("You can't gamble - you're the Card Czar this round.".to_string()));
+            }
+            if manager.is_final_round() {
+                return Err(CommandError("There's nothing left to gamble for.".to_string()));
+            }
+            if manager.get_wager(m.author.id).is_some() {
+                return Err(CommandError("You've already gambled this round.".to_string()));
+            }
+            let played = manager.get_cards_in_play(m.author.id).cloned().unwrap_or_default();
+            if played.is_empty() {
+                return Err(CommandError("Play your first answer with `cah play` before gambling on a second.".to_string()));
             }
+            if manager.get_wins(m.author.id).is_empty() {
+                return Err(CommandError("You haven't won any black cards to wager.".to_string()));
+            }
+            // everything that can go wrong is checked before select_cards, which makes the write-ins
+            let hand = manager.get_hand(m.author.id).clone();
+            if indices.iter().filter_map(|x| hand.get(*x)).any(|x| played.contains(x)) {
+                return Err(CommandError("You've already played that card.".to_string()));
+            }
+            let selection = select_cards(manager, m.author.id, &indices, &texts)?;
+            manager.place_wager(m.author.id);
+            manager.add_cards_in_play(m.author.id, selection.to_vec());
+            manager.get_primary_channel().say(format!("{} has wagered a black card on a second answer.", get_name(m)))?;
+            Ok(())
+        },
+    }
+}
+
+pub fn answer(c: &mut Context, m: &Message, mut a: Args) -> CommandResult {
+    let mut data = c.data.lock();
+    let manager = data.get_mut::<CahManager>().ok_or_else(|| CommandError("Couldn't load the CahManager".to_string()))?;
+    match manager.get_state() {
+        State::Off => Err(CommandError("The game is not running".to_string())),
+        State::Reading => Err(CommandError("Wait for the next round".to_string())),
+        State::Waiting => Err(CommandError("Wait for a black card to be chosen".to_string())),
+        State::Playing => {
+            if manager.get_mode() != GameMode::FreeText {
+                return Err(CommandError("This game is played with cards - use `cah play` instead.".to_string()));
+            }
+            if !manager.get_players().contains(m.author.id) {
+                return Err(CommandError("You are not in the game. (Type `cah join` to join)".to_string()));
+            }
+            if !manager.is_answering(m.author.id) {
+                return Err(CommandError("You can't answer - you're the Card Czar this round.".to_string()));
+            }
+            let play = manager.get_current_black_card().and_then(|x| manager.get_black_card(x)).map(|x| x.play as usize)
+                .ok_or_else(|| CommandError("Internal error: Game is in State::Playing but there is no current black card".to_string()))?;
+            // no need for quotes around a single answer
+            let texts = if play == 1 {
+                Some(a.full_quoted().to_string()).into_iter().collect::<SmallVec<[_; 5]>>()
+            } else {
+                a.iter_quoted::<String>().collect::<Result<SmallVec<[_; 5]>, _>>()?
+            };
+            if texts.len() != play {
+                return Err(CommandError(format!("Wrong number of answers (expected {}, got {}). Put each one in quotes.", play, texts.len())));
+            }
+            if texts.iter().any(|x| x.trim().is_empty() || x.chars().count() > MAX_WRITE_IN_LENGTH) {
+                return Err(CommandError(format!("Answers must be between 1 and {} characters long.", MAX_WRITE_IN_LENGTH)));
+            }
+            let selection = texts.into_iter().map(|x| manager.write_in(x.trim().to_string())).collect::<SmallVec<[_; 5]>>();
             {
                 let cards = manager.get_cards_in_play_mut(m.author.id);
                 cards.clear();
-                for idx in indices.iter() {
-                    cards.push(hand[*idx])
-                }
-            }
-            manager.get_primary_channel().say(format!("{} has played.", get_name(m)))?;
-            let czar = manager.get_players().current();
-            let mut done = true;
-            let not_czar = manager.get_players().all().iter().cloned().filter(|x| *x != czar).collect::<SmallVec<[_; 20]>>();
-            for id in not_czar.iter().cloned() {
-                if manager.get_cards_in_play(id).map(|x| x.is_empty()).unwrap_or(true) {
-                    done = false;
-                }
+                cards.extend(selection);
             }
-            if done {
-                manager.get_primary_channel().say(format!("Everyone has played. Now it's time for <@{}> to pick.", czar))?;
-                let mut string = String::new();
-                {
-                    let card = manager.get_black_card(card).unwrap();
-                    let mut values = manager.get_all_cards_in_play().map(|(id, x)| (id, x.iter().cloned().collect::<SmallVec<[WhiteCardId; 5]>>())).collect::<SmallVec<[_; 20]>>();
-                    values.sort_by(|x, y| x.1.cmp(&y.1));
-                    for (idx, cards) in values {
-                        let selection = cards.iter().map(|x| manager.get_white_card(*x).unwrap()).collect::<SmallVec<[_; 5]>>();
-                        write!(&mut string, "{}: {}\n", idx, &format_card(card, &selection))?;
-                    }
-                }
-                manager.get_primary_channel().say(string)?;
-                manager.set_state(State::Reading);
+            // the card's in either way, so a message that didn't send doesn't make the play look like it failed
+            if let Err(err) = reveal_if_done(c, m, manager) {
+                eprintln!("Error revealing the answers: {}", err.0);
             }
+            update_status(m, manager);
             Ok(())
         },
     }
 }
 
+// `cah play 1 4 "something"`: hand indices, then whatever's written on any blanks among them
+fn parse_play(a: &mut Args) -> Result<(SmallVec<[usize; 5]>, SmallVec<[String; 5]>), CommandError> {
+    let mut indices = SmallVec::new();
+    while let Ok(x) = a.single::<usize>() {
+        indices.push(x);
+    }
+    let texts = a.iter_quoted::<String>().collect::<Result<SmallVec<[_; 5]>, _>>()?;
+    Ok((indices, texts))
+}
+
+// checks a play against the current black card and resolves hand indices to cards, filling in any blanks
+fn select_cards(manager: &mut CahManager, user: UserId, indices: &[usize], texts: &[String]) -> Result<SmallVec<[WhiteCardId; 5]>, CommandError> {
+    let hand = manager.get_hand(user).iter().cloned().collect::<SmallVec<[_; 20]>>();
+    let card = manager.get_current_black_card().ok_or_else(|| CommandError("Internal error: Game is in State::Playing but there is no current black card".to_string()))?;
+    let card = manager.get_black_card(card).ok_or_else(|| CommandError("Internal error: Current black card is not a defined card".to_string()))?;
+    if (card.play as usize) != indices.len() {
+        return Err(CommandError(format!("Wrong number of cards played (expected {}, got {})", card.play, indices.len())))
+    }
+    if let Some(x) = indices.iter().cloned().find(|x| hand.len() <= *x) {
+        return Err(CommandError(format!("{} is not a card in your hand.", x)))
+    }
+    let invalid = indices.iter().filter(|x| manager.get_white_card(hand[**x]).is_none()).collect::<SmallVec<[_; 5]>>();
+    if invalid.len() != 0 {
+        let mut string = String::with_capacity(95);
+        string.push_str("Cards ");
+        for n in &invalid[..invalid.len() - 1] {
+            write!(&mut string, "{}, ", n)?;
+        }
+        write!(&mut string, "and {} are invalid (has the deck been reset?) Use `cah cards` to refresh.", invalid[invalid.len() - 1])?;
+        return Err(CommandError(string))
+    }
+    let blanks = indices.iter().filter(|x| manager.get_white_card(hand[**x]).map(|y| y.blank).unwrap_or(false)).count();
+    if blanks != texts.len() {
+        return Err(CommandError(if blanks == 0 {
+            "None of those cards are blank, so there's nothing to write on.".to_string()
+        } else {
+            format!("You played {} blank card(s), so write {} answer(s) in quotes after the card numbers (e.g. `cah play 3 \"your answer\"`)", blanks, blanks)
+        }));
+    }
+    if texts.iter().any(|x| x.trim().is_empty() || x.chars().count() > MAX_WRITE_IN_LENGTH) {
+        return Err(CommandError(format!("Written answers must be between 1 and {} characters long.", MAX_WRITE_IN_LENGTH)));
+    }
+    let mut texts = texts.iter();
+    let mut selection = SmallVec::new();
+    for idx in indices {
+        let card = hand[*idx];
+        if manager.get_white_card(card).unwrap().blank {
+            selection.push(manager.write_in(texts.next().unwrap().trim().to_string()));
+        } else {
+            selection.push(card);
+        }
+    }
+    Ok(selection)
+}
+
+// once everyone but the czar has answered, show the czar what they're picking from
+fn reveal_if_done(c: &Context, m: &Message, manager: &mut CahManager) -> CommandResult {
+    if manager.get_answering_players().iter().any(|x| manager.get_cards_in_play(*x).map(|x| x.is_empty()).unwrap_or(true)) {
+        return Ok(());
+    }
+    reveal(c, m, manager)
+}
+
+// the answers that are in, numbered for the czar or the voters
+fn reveal(c: &Context, m: &Message, manager: &mut CahManager) -> CommandResult {
+    let czar = manager.get_players().current();
+    if manager.get_judging() == Judging::Vote {
+        manager.get_primary_channel().say("Everyone has played. Vote for your favourite by reacting, or with `cah vote <number>`.")?;
+    } else {
+        manager.get_primary_channel().say(format!("Everyone has played. Now it's time for <@{}> to pick, by reacting or with `cah pick <number>`.", czar))?;
+    }
+    manager.shuffle_submissions();
+    let sent = {
+        let card = manager.get_current_black_card().and_then(|x| manager.get_black_card(x)).ok_or_else(|| CommandError("Internal error: Current black card is not a defined card".to_string()))?;
+        let mut message = CardMessage::new(display::black_card_colour(), display::escape(&card.message));
+        for (_, cards) in manager.get_submissions() {
+            let selection = cards.iter().map(|x| manager.get_white_card(*x).unwrap()).collect::<SmallVec<[_; 5]>>();
+            message.items.push(format_card(card, &selection));
+        }
+        message.footer = Some(round_footer(m, manager));
+        message.send(manager.get_primary_channel(), manager.is_plain_text())?
+    };
+    manager.set_state(State::Reading);
+    // e.g. one answer, from the only player who can see it. there's no vote to wait for
+    if manager.get_judging() == Judging::Vote && voters(manager).is_empty() {
+        manager.get_primary_channel().say("Nobody can vote on these, so they're placed as they are.")?;
+        return count_votes(c, m, manager);
+    }
+    if let Some(sent) = sent {
+        let emoji = (0..manager.get_submissions().len()).map(display::number_emoji).collect::<Option<Vec<_>>>();
+        // past 10 there aren't any emoji to go round, so it's commands only
+        if let Some(emoji) = emoji {
+            manager.set_submissions_message(Some(sent.id));
+            for emoji in emoji {
+                sent.react(ReactionType::Unicode(emoji))?;
+            }
+        }
+    }
+    Ok(())
+}
+
 pub fn pick_winner(c: &mut Context, m: &Message, mut a: Args) -> CommandResult {
     let mut data = c.data.lock();
     let manager = data.get_mut::<CahManager>().ok_or_else(|| CommandError("Couldn't load the CahManager".to_string()))?;
@@ -222,36 +1064,447 @@ pub fn pick_winner(c: &mut Context, m: &Message, mut a: Args) -> CommandResult {
         State::Waiting => Err(CommandError("The winner has already been picked".to_string())),
         State::Off => Err(CommandError("The game is not running".to_string())),
         State::Reading => {
-            if m.author.id != manager.get_players().current() {
-                return Err(CommandError("You're not the Card Czar.".to_string()));
-            }
-            let idx = a.single::<u8>()?;
-            let mut values = manager.get_all_cards_in_play().map(|(id, x)| (id, x.iter().cloned().collect::<SmallVec<[WhiteCardId; 5]>>())).collect::<SmallVec<[_; 20]>>();
-            values.sort_by(|x, y| x.1.cmp(&y.1));
-            let (id, selection) = &values[idx as usize];
-            let id = *id;
-            let black_card = manager.get_current_black_card().ok_or_else(|| CommandError("Internal error: State::Reading but no active black card".to_string()))?;
-            {
-                let black_card = manager.get_black_card(black_card).ok_or_else(|| CommandError("Internal error: Active black card is invalid".to_string()))?;
-                manager.get_primary_channel().say(format!("{} has chosen {}'s answer ({})", get_name(m), get_name_other(m, id), &format_card(black_card, &selection.iter().cloned().map(|x| manager.get_white_card(x).unwrap()).collect::<SmallVec<[_; 5]>>())))?;
-            }
-            let won = {
-                let win_condition = manager.get_win_condition();
-                let wins = manager.get_wins_mut(m.author.id);
-                wins.push(black_card);
-                wins.len() as u8 == win_condition
+            let picks = a.iter::<usize>().collect::<Result<SmallVec<[_; 3]>, _>>()?;
+            pick_answers(c, m, manager, m.author.id, &picks)
+        }
+    }
+}
+
+// the czar's picks, best first, from `cah pick` or reactions
+fn pick_answers(c: &Context, m: &Message, manager: &mut CahManager, czar: UserId, picks: &[usize]) -> CommandResult {
+    if manager.get_judging() == Judging::Vote {
+        return Err(CommandError("The players are voting on this one - use `cah vote`.".to_string()));
+    }
+    if czar != manager.get_players().current() {
+        return Err(CommandError("You're not the Card Czar.".to_string()));
+    }
+    let values = manager.get_submissions();
+    let ranked = ranked_picks(manager, values.len());
+    if picks.len() != ranked {
+        return Err(CommandError(format!("Pick your top {} answers, best first (e.g. `cah pick {}`)", ranked, (0..ranked).map(|x| x.to_string()).collect::<Vec<_>>().join(" "))));
+    }
+    if let Some(x) = picks.iter().cloned().find(|x| values.len() <= *x) {
+        return Err(CommandError(format!("{} is not one of the answers.", x)));
+    }
+    if picks.iter().enumerate().any(|(idx, x)| picks[..idx].contains(x)) {
+        return Err(CommandError("You can't pick the same answer twice.".to_string()));
+    }
+    finish_round(c, m, manager, &values, picks, &format!("{} has chosen", get_name_other(m, czar)))
+}
+
+pub fn vote(c: &mut Context, m: &Message, mut a: Args) -> CommandResult {
+    let idx = a.single::<usize>()?;
+    let mut data = c.data.lock();
+    let manager = data.get_mut::<CahManager>().ok_or_else(|| CommandError("Couldn't load the CahManager".to_string()))?;
+    match manager.get_state() {
+        State::Playing => Err(CommandError("Wait until everyone has played".to_string())),
+        State::Waiting => Err(CommandError("The winner has already been picked".to_string())),
+        State::Off => Err(CommandError("The game is not running".to_string())),
+        State::Reading => cast_vote(c, m, manager, m.author.id, idx),
+    }
+}
+
+// from `cah vote` or a reaction. the round ends once everyone who can vote has
+fn cast_vote(c: &Context, m: &Message, manager: &mut CahManager, voter: UserId, idx: usize) -> CommandResult {
+    if manager.get_judging() != Judging::Vote {
+        return Err(CommandError("The Card Czar is picking this one.".to_string()));
+    }
+    if !manager.get_players().contains(voter) {
+        return Err(CommandError("Only players can vote.".to_string()));
+    }
+    let values = manager.get_submissions();
+    if values.len() <= idx {
+        return Err(CommandError(format!("{} is not one of the answers.", idx)));
+    }
+    if values[idx].0 == voter {
+        return Err(CommandError("You can't vote for your own answer.".to_string()));
+    }
+    // a changed vote takes the old reaction away too, so there's only ever one to see
+    let previous = manager.get_votes().get(&voter).cloned().filter(|x| *x != idx);
+    if let (Some(previous), Some(message)) = (previous.and_then(display::number_emoji), manager.get_submissions_message()) {
+        manager.get_primary_channel().delete_reaction(message, Some(voter), ReactionType::Unicode(previous)).ok();
+    }
+    manager.cast_vote(voter, idx);
+    if voters(manager).iter().any(|x| !manager.get_votes().contains_key(x)) {
+        update_status(m, manager);
+        return Ok(());
+    }
+    count_votes(c, m, manager)
+}
+
+// places the answers by their votes. the submissions are shuffled, so a tie going to the lower number is as good as random
+fn count_votes(c: &Context, m: &Message, manager: &mut CahManager) -> CommandResult {
+    let values = manager.get_submissions();
+    let mut tally = vec![0usize; values.len()];
+    for vote in manager.get_votes().values() {
+        tally[*vote] += 1;
+    }
+    let mut order = (0..values.len()).collect::<Vec<_>>();
+    order.sort_by_key(|x| cmp::Reverse(tally[*x]));
+    order.truncate(ranked_picks(manager, values.len()));
+    let mut counts = order.iter().map(|x| tally[*x]).collect::<Vec<_>>();
+    counts.dedup();
+    let ties = counts.into_iter()
+        .map(|votes| (votes, (0..values.len()).filter(|x| tally[*x] == votes).map(|x| x.to_string()).collect::<Vec<_>>()))
+        .filter(|(_, tied)| tied.len() > 1)
+        .map(|(votes, tied)| format!("answers {} tied on {} vote{}", tied.join(", "), votes, if votes == 1 { "" } else { "s" }))
+        .collect::<Vec<_>>();
+    if !ties.is_empty() {
+        manager.get_primary_channel().say(format!("It's a tie: {}. Ties are broken at random.", ties.join("; ")))?;
+    }
+    finish_round(c, m, manager, &values, &order, "The votes are in! The players have chosen")
+}
+
+// anyone with only their own answers to choose from sits the vote out
+fn voters(manager: &CahManager) -> Vec<UserId> {
+    let values = manager.get_submissions();
+    manager.get_players().all().iter()
+        .filter(|x| values.iter().any(|(id, _)| id != *x))
+        .cloned()
+        .collect()
+}
+
+// a number reaction on the submissions is a pick or a vote. anyone who can't pick or vote has theirs taken away
+pub fn on_reaction(c: &Context, r: &Reaction) -> CommandResult {
+    let mut data = c.data.lock();
+    let manager = data.get_mut::<CahManager>().ok_or_else(|| CommandError("Couldn't load the CahManager".to_string()))?;
+    if manager.get_state() != State::Reading || manager.get_submissions_message() != Some(r.message_id) {
+        return Ok(());
+    }
+    // the bot's own reactions, put there to be clicked on
+    if r.user_id == CACHE.read().user.id {
+        return Ok(());
+    }
+    let eligible = match manager.get_judging() {
+        Judging::Czar => r.user_id == manager.get_players().current(),
+        Judging::Vote => manager.get_players().contains(r.user_id),
+    };
+    let idx = match r.emoji {
+        ReactionType::Unicode(ref emoji) => display::emoji_number(emoji),
+        _ => None,
+    };
+    let idx = match idx.filter(|x| *x < manager.get_submissions().len()) {
+        Some(idx) if eligible => idx,
+        _ => {
+            r.delete().ok();
+            return Ok(());
+        },
+    };
+    // names are looked up through a message, and the one that comes from the API doesn't say which server it's in
+    let mut m = r.message()?;
+    m.guild_id = manager.get_guild();
+    let result = match manager.get_judging() {
+        // a bad vote is between the bot and the voter, so it goes by DM instead of in the channel
+        Judging::Vote => {
+            if let Err(err) = cast_vote(c, &m, manager, r.user_id, idx) {
+                r.delete().ok();
+                r.user_id.create_dm_channel()?.say(display::escape_mentions(&err.0))?;
+            }
+            return Ok(());
+        },
+        Judging::Czar => {
+            let ranked = ranked_picks(manager, manager.get_submissions().len());
+            let picks = {
+                let picks = manager.get_reaction_picks_mut();
+                if !picks.contains(&idx) {
+                    picks.push(idx);
+                }
+                picks.clone()
             };
-            if won {
-                manager.get_primary_channel().say(format!("{} has won the game! 🎉", get_name(m)))?;
-                manager.set_state(State::Off);
-                c.reset_presence();
+            if picks.len() < ranked {
+                return Ok(());
+            }
+            manager.get_reaction_picks_mut().clear();
+            pick_answers(c, &m, manager, r.user_id, &picks)
+        },
+    };
+    if result.is_err() {
+        r.delete().ok();
+    }
+    result
+}
+
+// taking a reaction back takes back the pick or vote it made
+pub fn on_reaction_remove(c: &Context, r: &Reaction) -> CommandResult {
+    let mut data = c.data.lock();
+    let manager = data.get_mut::<CahManager>().ok_or_else(|| CommandError("Couldn't load the CahManager".to_string()))?;
+    if manager.get_state() != State::Reading || manager.get_submissions_message() != Some(r.message_id) {
+        return Ok(());
+    }
+    let idx = match r.emoji {
+        ReactionType::Unicode(ref emoji) => display::emoji_number(emoji),
+        _ => None,
+    };
+    let idx = match idx {
+        Some(idx) => idx,
+        None => return Ok(()),
+    };
+    match manager.get_judging() {
+        Judging::Czar => if r.user_id == manager.get_players().current() {
+            manager.get_reaction_picks_mut().retain(|x| *x != idx);
+        },
+        // changing a vote takes the old reaction away too, which shouldn't take the new vote with it
+        Judging::Vote => if manager.get_votes().get(&r.user_id) == Some(&idx) {
+            manager.remove_vote(r.user_id);
+            // names are looked up through a message, same as in on_reaction
+            let mut m = r.message()?;
+            m.guild_id = manager.get_guild();
+            update_status(&m, manager);
+        },
+    }
+    Ok(())
+}
+
+// how many answers get placed this round
+fn ranked_picks(manager: &CahManager, answers: usize) -> usize {
+    if manager.has_rule(HouseRule::SeriousBusiness) && !manager.is_final_round() {
+        cmp::min(SERIOUS_BUSINESS_POINTS.len(), answers)
+    } else {
+        1
+    }
+}
+
+// announces the picked answers (best first), hands out the points and moves on to the next round or ends the game
+fn finish_round(c: &Context, m: &Message, manager: &mut CahManager, values: &[(UserId, SmallVec<[WhiteCardId; 5]>)], picks: &[usize], chosen_by: &str) -> CommandResult {
+    let final_round = manager.is_final_round();
+    let serious_business = manager.has_rule(HouseRule::SeriousBusiness) && !final_round;
+    let black_card = manager.get_current_black_card().ok_or_else(|| CommandError("Internal error: State::Reading but no active black card".to_string()))?;
+    {
+        let black_card = manager.get_black_card(black_card).ok_or_else(|| CommandError("Internal error: Active black card is invalid".to_string()))?;
+        for (rank, idx) in picks.iter().enumerate() {
+            let (id, selection) = &values[*idx];
+            let fills = selection.iter().cloned().map(|x| manager.get_white_card(x).unwrap()).collect::<SmallVec<[_; 5]>>();
+            let text = format_card(black_card, &fills);
+            if rank == 0 {
+                let announcement = format!("{} {}'s answer ({})", chosen_by, get_name_other(m, *id), &text);
+                if !send_card_image(manager, manager.get_primary_channel(), black_card, &fills, &announcement)? {
+                    display::say(manager.get_primary_channel(), &announcement)?;
+                }
             } else {
-                manager.clear_cards_in_play();
-                manager.set_state(State::Waiting);
-                let next = manager.get_players_mut().next_player();
-                manager.get_primary_channel().say(format!("It's <@{}>'s turn.", next.0))?;
+                display::say(manager.get_primary_channel(), &format!("Runner-up #{}: {}'s answer ({})", rank, get_name_other(m, *id), &text))?;
             }
-            Ok(())
         }
     }
+    manager.clear_votes();
+    if final_round {
+        // nothing to award, the game's already been won
+        manager.get_primary_channel().say(format!("{} goes out in glory. Thanks for playing!", get_name_other(m, values[picks[0]].0)))?;
+        end_game(c, manager);
+        update_status(m, manager);
+        return Ok(());
+    }
+    let round_winner = values[picks[0]].0;
+    for (rank, idx) in picks.iter().enumerate() {
+        let points = if serious_business { SERIOUS_BUSINESS_POINTS[rank] } else { 1 };
+        if rank == 0 {
+            manager.add_win(round_winner, black_card, points);
+        }
+        manager.add_points(values[*idx].0, points);
+    }
+    for (gambler, (wager, points)) in manager.take_wagers() {
+        // the wager is worth the points it was taken with
+        manager.add_win(round_winner, wager, points);
+        manager.add_points(round_winner, points);
+        if gambler != round_winner {
+            manager.get_primary_channel().say(format!("{} lost their wager to {}.", get_name_other(m, gambler), get_name_other(m, round_winner)))?;
+        }
+    }
+    let win_condition = manager.get_win_condition();
+    let won = manager.get_scores().filter(|(_, x)| *x >= win_condition).max_by_key(|(_, x)| *x);
+    manager.clear_cards_in_play();
+    // the next round, or the end of the game, takes it from here
+    manager.set_state(State::Waiting);
+    update_status(m, manager);
+    if let Some((winner, _)) = won {
+        manager.get_primary_channel().say(format!("{} has won the game! 🎉", get_name_other(m, winner)))?;
+        if manager.has_rule(HouseRule::HappyEnding) {
+            manager.get_primary_channel().say("But first, one last round for glory:")?;
+            manager.set_final_round(true);
+            manager.get_players_mut().next_player();
+            let channel = manager.get_primary_channel();
+            let id = manager.inject_black_card(haiku_card());
+            start_round(c, m, manager, channel, id)?;
+        } else {
+            end_game(c, manager);
+        }
+    } else {
+        let next = manager.get_players_mut().next_player();
+        manager.get_primary_channel().say(format!("It's <@{}>'s turn.", next.0))?;
+    }
+    Ok(())
+}
+
+// what's happening this round: the black card, who's played or voted and who hasn't, and the scores
+fn status_board(m: &Message, manager: &CahManager) -> CardMessage {
+    let mut message = CardMessage::new(display::black_card_colour(), format!("Round {}", manager.get_round()));
+    let mut lines = Vec::new();
+    if let Some(card) = manager.get_current_black_card().and_then(|x| manager.get_black_card(x)) {
+        lines.push(display::escape(&card.message));
+        lines.push(String::new());
+    }
+    let czar = manager.get_players().current();
+    match manager.get_state() {
+        State::Playing => {
+            if manager.get_judging() == Judging::Czar {
+                lines.push(format!("Card Czar: {}", get_name_other(m, czar)));
+            }
+            push_progress(&mut lines, m, "Played", manager.get_answering_players().into_iter()
+                .partition(|x| manager.get_cards_in_play(*x).map(|x| !x.is_empty()).unwrap_or(false)));
+        },
+        State::Reading => match manager.get_judging() {
+            Judging::Czar => lines.push(format!("Waiting for {} to pick.", get_name_other(m, czar))),
+            Judging::Vote => push_progress(&mut lines, m, "Voted", voters(manager).into_iter().partition(|x| manager.get_votes().contains_key(x))),
+        },
+        State::Waiting => lines.push("Round over.".to_string()),
+        State::Off => lines.push("The game is over.".to_string()),
+    }
+    // the board's only redrawn when something happens, so what's left can go stale. the end time doesn't
+    if let (State::Playing, Some(deadline)) = (manager.get_state(), manager.get_round_deadline()) {
+        let left = deadline.duration_since(SystemTime::now()).map(|x| x.as_secs()).unwrap_or(0);
+        let end = deadline.duration_since(UNIX_EPOCH).map(|x| x.as_secs()).unwrap_or(0);
+        lines.push(format!("Time left: {}m {:02}s (ends {:02}:{:02} UTC)", left / 60, left % 60, end / 3600 % 24, end / 60 % 60));
+    }
+    message.description = lines.join("\n");
+    message.footer = Some(round_footer(m, manager));
+    message
+}
+
+// who's done and who isn't, leaving out either list if it's empty
+fn push_progress(lines: &mut Vec<String>, m: &Message, done_label: &str, (done, waiting): (Vec<UserId>, Vec<UserId>)) {
+    let names = |ids: &[UserId]| ids.iter().map(|x| get_name_other(m, *x)).collect::<Vec<_>>().join(", ");
+    if !done.is_empty() {
+        lines.push(format!("{}: {}", done_label, names(&done)));
+    }
+    if !waiting.is_empty() {
+        lines.push(format!("Waiting on: {}", names(&waiting)));
+    }
+}
+
+// brings the board up to date, if there is one. it's only a summary, so failing to edit it never fails the command
+fn update_status(m: &Message, manager: &CahManager) {
+    if let Some(id) = manager.get_status_message() {
+        if let Err(err) = status_board(m, manager).edit(manager.get_primary_channel(), id, manager.is_plain_text()) {
+            eprintln!("Error updating the status board: {}", err);
+        }
+    }
+}
+
+// posts the board again at the bottom of the channel, for when it's scrolled away
+pub fn show_status(c: &mut Context, m: &Message, _a: Args) -> CommandResult {
+    let mut data = c.data.lock();
+    let manager = data.get_mut::<CahManager>().ok_or_else(|| CommandError("Couldn't load the CahManager".to_string()))?;
+    if !manager.is_running() {
+        return Err(CommandError("A game is not running!".to_string()));
+    }
+    let channel = manager.get_primary_channel();
+    let sent = status_board(m, manager).send(channel, manager.is_plain_text())?;
+    if let Some(old) = manager.get_status_message() {
+        channel.delete_message(old).ok();
+    }
+    manager.set_status_message(sent.map(|x| x.id));
+    Ok(())
+}
+
+fn end_game(c: &Context, manager: &mut CahManager) {
+    manager.set_final_round(false);
+    manager.set_excluded_decks(HashSet::new());
+    manager.set_state(State::Off);
+    c.reset_presence();
+}
+
+pub fn set_rule(c: &mut Context, m: &Message, mut a: Args) -> CommandResult {
+    let mut data = c.data.lock();
+    let manager = data.get_mut::<CahManager>().ok_or_else(|| CommandError("Couldn't load the CahManager".to_string()))?;
+    let rule = a.single::<String>()?.parse::<HouseRule>().map_err(CommandError)?;
+    let enabled = match a.single::<String>().as_ref().map(|x| x.as_str()) {
+        Ok("on") => true,
+        Ok("off") => false,
+        _ => return Err(CommandError("Expected 'on' or 'off'".to_string())),
+    };
+    if manager.get_state() == State::Playing || manager.get_state() == State::Reading {
+        return Err(CommandError("You can't change the house rules while a black card is in play.".to_string()));
+    }
+    manager.set_rule(rule, enabled);
+    let mut string = format!("House rule {} is now {}.", rule, if enabled { "on" } else { "off" });
+    // the win target is in points, and these come a few at a time
+    if rule == HouseRule::SeriousBusiness && enabled {
+        let per_round = SERIOUS_BUSINESS_POINTS.iter().sum::<u32>();
+        write!(&mut string, " That's up to {} points a round (the winner gets {}), and the game is still to {}. Raise it with `cah set-win`, e.g. `cah set-win {}`.",
+            per_round, SERIOUS_BUSINESS_POINTS[0], manager.get_win_condition(), manager.get_win_condition() * SERIOUS_BUSINESS_POINTS[0])?;
+    }
+    m.channel_id.say(string)?;
+    Ok(())
+}
+
+pub fn get_rules(c: &mut Context, m: &Message, _a: Args) -> CommandResult {
+    let data = c.data.lock();
+    let manager = data.get::<CahManager>().ok_or_else(|| CommandError("Couldn't load the CahManager".to_string()))?;
+    let mut string = format!("Mode: {}\nJudging: {}\nPoints to win: {}\nHouse rules:\n\n", manager.get_mode(), manager.get_judging(), manager.get_win_condition());
+    for rule in HouseRule::ALL {
+        writeln!(&mut string, "{}: {}", rule, if manager.has_rule(*rule) { "on" } else { "off" })?;
+    }
+    display::say(m.channel_id, &string)?;
+    Ok(())
+}
+
+pub fn set_win_condition(c: &mut Context, m: &Message, mut a: Args) -> CommandResult {
+    let points = a.single::<u32>()?;
+    let mut data = c.data.lock();
+    let manager = data.get_mut::<CahManager>().ok_or_else(|| CommandError("Couldn't load the CahManager".to_string()))?;
+    if points == 0 {
+        return Err(CommandError("The game needs at least one point to win.".to_string()));
+    }
+    manager.set_win_condition(points);
+    m.channel_id.say(format!("The first player to {} points wins.", points))?;
+    Ok(())
+}
+
+pub fn set_round_timer(c: &mut Context, m: &Message, mut a: Args) -> CommandResult {
+    let minutes = a.single::<u64>().map_err(|_| CommandError("Usage: `cah set-timer <minutes>` (0 for no limit)".to_string()))?;
+    let mut data = c.data.lock();
+    let manager = data.get_mut::<CahManager>().ok_or_else(|| CommandError("Couldn't load the CahManager".to_string()))?;
+    if minutes == 0 {
+        manager.set_round_time_limit(None);
+        m.channel_id.say("Rounds have no time limit, starting next round.")?;
+    } else {
+        manager.set_round_time_limit(Some(Duration::from_secs(minutes * 60)));
+        m.channel_id.say(format!("Rounds will last {} minute{}, starting next round. Anyone who hasn't played by then sits it out.", minutes, if minutes == 1 { "" } else { "s" }))?;
+    }
+    Ok(())
+}
+
+pub fn set_blank_cards(c: &mut Context, m: &Message, mut a: Args) -> CommandResult {
+    let blank_cards = a.single::<u8>()?;
+    let mut data = c.data.lock();
+    let manager = data.get_mut::<CahManager>().ok_or_else(|| CommandError("Couldn't load the CahManager".to_string()))?;
+    manager.set_blank_cards(blank_cards);
+    m.channel_id.say(format!("Each deck will come with {} blank cards, starting next game or the next time the decks change.", blank_cards))?;
+    Ok(())
+}
+
+pub fn set_mode(c: &mut Context, m: &Message, mut a: Args) -> CommandResult {
+    let mode = a.single::<String>()?.parse::<GameMode>().map_err(CommandError)?;
+    let mut data = c.data.lock();
+    let manager = data.get_mut::<CahManager>().ok_or_else(|| CommandError("Couldn't load the CahManager".to_string()))?;
+    if manager.is_running() {
+        return Err(CommandError("You can't change the game mode while a game is running.".to_string()));
+    }
+    manager.set_mode(mode);
+    m.channel_id.say(format!("The next game will be played in {} mode.", mode))?;
+    Ok(())
+}
+
+pub fn set_judging(c: &mut Context, m: &Message, mut a: Args) -> CommandResult {
+    let judging = a.single::<String>()?.parse::<Judging>().map_err(CommandError)?;
+    let mut data = c.data.lock();
+    let manager = data.get_mut::<CahManager>().ok_or_else(|| CommandError("Couldn't load the CahManager".to_string()))?;
+    if manager.get_state() == State::Playing || manager.get_state() == State::Reading {
+        return Err(CommandError("You can't change how answers are judged while a black card is in play.".to_string()));
+    }
+    manager.set_judging(judging);
+    m.channel_id.say(match judging {
+        Judging::Czar => "The Card Czar will pick the winning answers.",
+        Judging::Vote => "Everyone will answer and vote for the winning answers.",
+    })?;
+    Ok(())
 }
@@ -0,0 +1,862 @@
+use csv::{Reader, StringRecord, WriterBuilder};
+use csv::Error as CsvError;
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use std::collections::HashMap;
+use std::collections::hash_map::Entry;
+use std::fmt::{Display, Formatter, Result as FmtResult};
+use std::fs::{self, File};
+use std::io::{Read, Write};
+use std::io::{Error as IoError, ErrorKind};
+use std::cmp;
+use serde_json::{self, Error as JsonError};
+use serenity::model::id::GuildId;
+use std::str;
+use std::path::{Path, PathBuf};
+use super::{BlackCard, BlackCardId, WhiteCard, WhiteCardId, count_blanks, stable_hash};
+
+pub const DECK_ROOT: &str = "decks";
+pub const GUILD_ROOT: &str = "guilds";
+
+const WHITE_COLUMNS: &[&str] = &["id", "message", "tags"];
+const BLACK_COLUMNS: &[&str] = &["id", "draw", "play", "message", "tags"];
+
+// a row of white.csv
+#[derive(Serialize, Deserialize)]
+struct WhiteCardRecord {
+    #[serde(default)]
+    id: Option<u64>,
+    message: String,
+    #[serde(default)]
+    tags: String,
+}
+
+impl WhiteCardRecord {
+    fn into_card(self, deck: &str) -> WhiteCard {
+        WhiteCard {
+            id: WhiteCardId::new(deck, self.id.unwrap_or_else(|| derive_id(&self.message))),
+            tags: parse_tags(&self.tags),
+            message: self.message,
+            blank: false,
+        }
+    }
+}
+
+// a row of black.csv
+#[derive(Serialize, Deserialize)]
+struct BlackCardRecord {
+    #[serde(default)]
+    id: Option<u64>,
+    draw: u8,
+    play: u8,
+    message: String,
+    #[serde(default)]
+    tags: String,
+}
+
+impl BlackCardRecord {
+    fn into_card(self, deck: &str) -> BlackCard {
+        BlackCard {
+            id: BlackCardId::new(deck, self.id.unwrap_or_else(|| derive_id(&self.message))),
+            tags: parse_tags(&self.tags),
+            message: self.message,
+            draw: self.draw,
+            play: self.play,
+        }
+    }
+}
+
+// the tags column is a list separated by semicolons, e.g. `politics; pop-culture`
+fn parse_tags(tags: &str) -> Vec<String> {
+    tags.split(';').map(|x| x.trim().to_lowercase()).filter(|x| !x.is_empty()).collect()
+}
+
+// for decks without an id column. the text is the one thing that identifies a card no matter where the row ends up
+fn derive_id(message: &str) -> u64 {
+    stable_hash(message.trim())
+}
+
+pub fn parse_white_cards<R>(read: R, deck: &str) -> Result<Vec<WhiteCard>, CsvError> where R: Read {
+    let mut reader = Reader::from_reader(read);
+    let mut vec = Vec::new();
+    for record in reader.deserialize::<WhiteCardRecord>() {
+        vec.push(record?.into_card(deck))
+    }
+    Ok(vec)
+}
+
+// the other way around from parse_white_cards, in the same columns
+pub fn write_white_cards<W>(write: W, cards: &[WhiteCard]) -> Result<(), IoError> where W: Write {
+    let records = cards.iter()
+        .map(|x| WhiteCardRecord { id: Some(x.id.number()), message: x.message.clone(), tags: x.tags.join(";") })
+        .collect::<Vec<_>>();
+    write_records_to(write, &records)
+}
+
+pub fn write_black_cards<W>(write: W, cards: &[BlackCard]) -> Result<(), IoError> where W: Write {
+    let records = cards.iter()
+        .map(|x| BlackCardRecord { id: Some(x.id.number()), draw: x.draw, play: x.play, message: x.message.clone(), tags: x.tags.join(";") })
+        .collect::<Vec<_>>();
+    write_records_to(write, &records)
+}
+
+pub fn parse_black_cards<R>(read: R, deck: &str) -> Result<Vec<BlackCard>, CsvError> where R: Read {
+    let mut reader = Reader::from_reader(read);
+    let mut vec = Vec::new();
+    for record in reader.deserialize::<BlackCardRecord>() {
+        vec.push(record?.into_card(deck))
+    }
+    Ok(vec)
+}
+
+// a lone _ becomes ____, longer runs are already blanks
+fn widen_blanks(text: &str) -> String {
+    let mut widened = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    let mut after_underscore = false;
+    while let Some(c) = chars.next() {
+        if c == '_' && !after_underscore && chars.peek() != Some(&'_') {
+            widened.push_str("____");
+        } else {
+            widened.push(c);
+        }
+        after_underscore = c == '_';
+    }
+    widened
+}
+
+// the JSON dumps of the community card databases. there are a few variations on the format:
+// a single pack or a list of them, `black`/`white` or `calls`/`responses`, and card text as either
+// a string or (cardcast-style) a list of the bits between the blanks
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum JsonDeck {
+    Packs(Vec<JsonPack>),
+    Pack(JsonPack),
+}
+
+#[derive(Deserialize)]
+struct JsonPack {
+    #[serde(default, alias = "blacks", alias = "calls")]
+    black: Vec<JsonCard>,
+    #[serde(default, alias = "whites", alias = "responses")]
+    white: Vec<JsonCard>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum JsonCard {
+    Text(JsonText),
+    Card {
+        text: JsonText,
+        #[serde(default)]
+        id: Option<u64>,
+        #[serde(default)]
+        pick: Option<u8>,
+        #[serde(default)]
+        draw: Option<u8>,
+        #[serde(default)]
+        tags: Vec<String>,
+    },
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum JsonText {
+    Whole(String),
+    Parts(Vec<String>),
+}
+
+impl JsonText {
+    fn join(self, blank: &str) -> String {
+        match self {
+            JsonText::Whole(text) => text,
+            JsonText::Parts(parts) => parts.join(blank),
+        }
+    }
+}
+
+impl JsonCard {
+    fn into_white_record(self) -> WhiteCardRecord {
+        match self {
+            JsonCard::Text(text) => WhiteCardRecord { id: None, message: text.join(""), tags: String::new() },
+            JsonCard::Card { text, id, tags, .. } => WhiteCardRecord { id, message: text.join(""), tags: tags.join(";") },
+        }
+    }
+    fn into_black_record(self) -> BlackCardRecord {
+        let (text, id, pick, draw, tags) = match self {
+            JsonCard::Text(text) => (text, None, None, None, Vec::new()),
+            JsonCard::Card { text, id, pick, draw, tags } => (text, id, pick, draw, tags),
+        };
+        // the JSON Against Humanity dumps write a blank as a single underscore, which count_blanks doesn't count
+        let message = match text {
+            JsonText::Whole(text) => widen_blanks(&text),
+            parts => parts.join("____"),
+        };
+        BlackCardRecord {
+            id,
+            draw: draw.unwrap_or(0),
+            play: pick.unwrap_or_else(|| cmp::max(1, count_blanks(&message) as u8)),
+            message,
+            tags: tags.join(";"),
+        }
+    }
+}
+
+fn parse_json_records<R>(read: R) -> Result<(Vec<BlackCardRecord>, Vec<WhiteCardRecord>), JsonError> where R: Read {
+    let packs = match serde_json::from_reader(read)? {
+        JsonDeck::Packs(packs) => packs,
+        JsonDeck::Pack(pack) => vec![pack],
+    };
+    let mut black = Vec::new();
+    let mut white = Vec::new();
+    for pack in packs {
+        black.extend(pack.black.into_iter().map(JsonCard::into_black_record));
+        white.extend(pack.white.into_iter().map(JsonCard::into_white_record));
+    }
+    Ok((black, white))
+}
+
+pub fn parse_json_deck<R>(read: R, deck: &str) -> Result<(Vec<BlackCard>, Vec<WhiteCard>), JsonError> where R: Read {
+    let (black, white) = parse_json_records(read)?;
+    Ok((black.into_iter().map(|x| x.into_card(deck)).collect(), white.into_iter().map(|x| x.into_card(deck)).collect()))
+}
+
+// everything a guild has set up for itself
+pub fn guild_root(guild: GuildId) -> PathBuf {
+    Path::new(GUILD_ROOT).join(guild.0.to_string())
+}
+
+// decks a guild has uploaded or made itself, only usable in that guild
+pub fn guild_deck_root(guild: GuildId) -> PathBuf {
+    guild_root(guild).join(DECK_ROOT)
+}
+
+// deck names end up in file paths, so keep them boring
+pub fn is_valid_deck_name(name: &str) -> bool {
+    !name.is_empty() && name.chars().all(|c: char| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+}
+
+fn deck_exists(root: &Path, name: &str) -> bool {
+    root.join(name).is_dir() || root.join(format!("{}.json", name)).exists()
+}
+
+pub fn is_builtin_deck(name: &str) -> bool {
+    deck_exists(Path::new(DECK_ROOT), name)
+}
+
+// the folder the deck is in. guild decks can't be named after built-in ones, so which goes first doesn't really matter
+fn deck_root(name: &str, guild: Option<GuildId>) -> PathBuf {
+    if let Some(guild) = guild {
+        let root = guild_deck_root(guild);
+        if deck_exists(&root, name) {
+            return root;
+        }
+    }
+    PathBuf::from(DECK_ROOT)
+}
+
+// where a deck's cards are kept
+enum DeckSource {
+    // a folder with black.csv and white.csv
+    Csv(PathBuf),
+    // decks/<name>/cards.json, or just decks/<name>.json if there's no need for a deck.toml
+    Json(PathBuf),
+}
+
+fn find_deck(name: &str, guild: Option<GuildId>) -> Result<DeckSource, IoError> {
+    if !is_valid_deck_name(name) {
+        return Err(IoError::new(ErrorKind::NotFound, format!("There's no deck called {}", name)));
+    }
+    let root = deck_root(name, guild);
+    let dir = root.join(name);
+    if dir.join("white.csv").exists() || dir.join("black.csv").exists() {
+        return Ok(DeckSource::Csv(dir));
+    }
+    let json = dir.join("cards.json");
+    if json.exists() {
+        return Ok(DeckSource::Json(json));
+    }
+    let json = root.join(format!("{}.json", name));
+    if json.exists() {
+        return Ok(DeckSource::Json(json));
+    }
+    Err(IoError::new(ErrorKind::NotFound, format!("There's no deck called {}", name)))
+}
+
+pub fn load_deck(name: &str, guild: Option<GuildId>) -> Result<(Vec<BlackCard>, Vec<WhiteCard>), IoError> {
+    match find_deck(name, guild)? {
+        DeckSource::Csv(dir) => {
+            let white_file = File::open(dir.join("white.csv"))?;
+            let white = parse_white_cards(white_file, name)?;
+            let black_file = File::open(dir.join("black.csv"))?;
+            let black = parse_black_cards(black_file, name)?;
+            Ok((black, white))
+        },
+        DeckSource::Json(path) => Ok(parse_json_deck(File::open(path)?, name)?),
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug, Hash)]
+#[serde(rename_all = "kebab-case")]
+pub enum ContentRating {
+    Family,
+    Teen,
+    Mature,
+}
+
+impl Default for ContentRating {
+    // no manifest says otherwise, so assume the worst
+    fn default() -> Self {
+        ContentRating::Mature
+    }
+}
+
+impl Display for ContentRating {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        match self {
+            ContentRating::Family => f.write_str("family"),
+            ContentRating::Teen => f.write_str("teen"),
+            ContentRating::Mature => f.write_str("mature"),
+        }
+    }
+}
+
+// deck.toml, everything about a deck that isn't the cards. all of it is optional
+#[derive(Serialize, Deserialize, Clone, Default, Debug)]
+#[serde(rename_all = "kebab-case", default)]
+pub struct DeckManifest {
+    pub name: Option<String>,
+    pub description: Option<String>,
+    pub author: Option<String>,
+    pub license: Option<String>,
+    pub attribution: Option<String>,
+    pub language: Option<String>,
+    pub rating: ContentRating,
+    pub tags: Vec<String>,
+    // decks that have to be loaded along with this one, e.g. expansions need base
+    pub dependencies: Vec<String>,
+}
+
+pub fn load_manifest(name: &str, guild: Option<GuildId>) -> Result<DeckManifest, IoError> {
+    // checks the name and that there's a deck there at all, so a missing deck isn't mistaken for one without a manifest
+    find_deck(name, guild)?;
+    let path = deck_root(name, guild).join(name).join("deck.toml");
+    if !path.exists() {
+        return Ok(DeckManifest::default());
+    }
+    let mut string = String::new();
+    File::open(&path)?.read_to_string(&mut string)?;
+    toml::from_str(&string).map_err(|x| IoError::new(ErrorKind::InvalidData, format!("{}: {}", path.display(), x)))
+}
+
+// the decks plus everything they depend on, dependencies first
+pub fn resolve_dependencies(names: &[String], guild: Option<GuildId>) -> Result<Vec<String>, IoError> {
+    resolve_with(names, |name| load_manifest(name, guild).map(|x| x.dependencies))
+}
+
+// resolve_dependencies, with the manifests coming from anywhere
+fn resolve_with<F>(names: &[String], dependencies: F) -> Result<Vec<String>, IoError> where F: Fn(&str) -> Result<Vec<String>, IoError> {
+    fn visit<F>(name: &str, dependencies: &F, resolved: &mut Vec<String>, visiting: &mut Vec<String>) -> Result<(), IoError> where F: Fn(&str) -> Result<Vec<String>, IoError> {
+        if resolved.iter().any(|x| x == name) {
+            return Ok(());
+        }
+        if visiting.iter().any(|x| x == name) {
+            return Err(IoError::new(ErrorKind::InvalidData, format!("Deck {} depends on itself", name)));
+        }
+        visiting.push(name.to_string());
+        for dependency in dependencies(name)? {
+            visit(&dependency, dependencies, resolved, visiting)?;
+        }
+        visiting.pop();
+        resolved.push(name.to_string());
+        Ok(())
+    }
+    let mut resolved = Vec::new();
+    for name in names {
+        visit(name, &dependencies, &mut resolved, &mut Vec::new())?;
+    }
+    Ok(resolved)
+}
+
+// every deck the guild can use, whether or not it's loaded
+pub fn available_decks(guild: Option<GuildId>) -> Result<Vec<String>, IoError> {
+    let mut names = list_decks(Path::new(DECK_ROOT))?;
+    if let Some(guild) = guild {
+        names.extend(guild_decks(guild)?);
+    }
+    names.sort();
+    names.dedup();
+    Ok(names)
+}
+
+pub fn guild_decks(guild: GuildId) -> Result<Vec<String>, IoError> {
+    let root = guild_deck_root(guild);
+    if root.exists() {
+        list_decks(&root)
+    } else {
+        Ok(Vec::new())
+    }
+}
+
+fn list_decks(root: &Path) -> Result<Vec<String>, IoError> {
+    let mut names = Vec::new();
+    for entry in fs::read_dir(root)? {
+        let path = entry?.path();
+        let name = if path.is_dir() {
+            path.file_name()
+        } else if path.extension().map(|x| x == "json").unwrap_or(false) {
+            path.file_stem()
+        } else {
+            None
+        };
+        if let Some(name) = name.and_then(|x| x.to_str()) {
+            names.push(name.to_string());
+        }
+    }
+    names.sort();
+    names.dedup();
+    Ok(names)
+}
+
+pub struct Problem {
+    pub file: PathBuf,
+    pub line: Option<u64>,
+    pub message: String,
+}
+
+impl Display for Problem {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        match self.line {
+            Some(line) => write!(f, "{}:{}: {}", self.file.display(), line, self.message),
+            None => write!(f, "{}: {}", self.file.display(), self.message),
+        }
+    }
+}
+
+// reads the decks the same way load_deck does, but carries on past problems so they can all be reported at once
+pub fn check_decks(names: &[String], guild: Option<GuildId>) -> Vec<Problem> {
+    let mut problems = Vec::new();
+    for name in names {
+        let root = deck_root(name, guild).join(name);
+        if let Err(err) = load_manifest(name, guild) {
+            problems.push(Problem { file: root.join("deck.toml"), line: None, message: err.to_string() });
+        }
+        match find_deck(name, guild) {
+            Ok(DeckSource::Csv(dir)) => {
+                check_file::<WhiteCardRecord>(&dir.join("white.csv"), &mut problems);
+                check_file::<BlackCardRecord>(&dir.join("black.csv"), &mut problems);
+            },
+            Ok(DeckSource::Json(path)) => match File::open(&path) {
+                Ok(file) => check_json(&path, file, &mut problems),
+                Err(err) => problems.push(Problem { file: path, line: None, message: err.to_string() }),
+            },
+            Err(err) => problems.push(Problem { file: root, line: None, message: err.to_string() }),
+        }
+    }
+    problems
+}
+
+trait CheckedCard: DeserializeOwned {
+    const COLUMNS: &'static [&'static str];
+    fn id(&self) -> u64;
+    fn message(&self) -> &str;
+    fn problem(&self) -> Option<String> {
+        None
+    }
+}
+
+impl CheckedCard for WhiteCardRecord {
+    const COLUMNS: &'static [&'static str] = WHITE_COLUMNS;
+    fn id(&self) -> u64 {
+        self.id.unwrap_or_else(|| derive_id(&self.message))
+    }
+    fn message(&self) -> &str {
+        &self.message
+    }
+}
+
+impl CheckedCard for BlackCardRecord {
+    const COLUMNS: &'static [&'static str] = BLACK_COLUMNS;
+    fn id(&self) -> u64 {
+        self.id.unwrap_or_else(|| derive_id(&self.message))
+    }
+    fn message(&self) -> &str {
+        &self.message
+    }
+    fn problem(&self) -> Option<String> {
+        let blanks = count_blanks(&self.message);
+        if self.play == 0 {
+            Some("play must be at least 1".to_string())
+        } else if blanks != 0 && blanks != self.play as usize {
+            // no blanks at all is fine, the answers just go after the question
+            Some(format!("play is {} but the card has {} blanks", self.play, blanks))
+        } else {
+            None
+        }
+    }
+}
+
+// what's wrong with a single card, whatever kind of file it's from. `ids` is where each id was first seen
+fn check_card<C: CheckedCard>(card: &C, at: String, ids: &mut HashMap<u64, String>) -> Vec<String> {
+    let mut problems = Vec::new();
+    if card.message().trim().is_empty() {
+        problems.push("empty message".to_string());
+    }
+    if let Some(problem) = card.problem() {
+        problems.push(problem);
+    }
+    // ids are namespaced by deck, so they only have to be unique within the file
+    match ids.entry(card.id()) {
+        Entry::Occupied(entry) => problems.push(format!("duplicate id {} (first used on {})", entry.key(), entry.get())),
+        Entry::Vacant(entry) => {
+            entry.insert(at);
+        },
+    }
+    problems
+}
+
+fn check_json<R>(path: &Path, read: R, problems: &mut Vec<Problem>) where R: Read {
+    match parse_json_records(read) {
+        Ok((black, white)) => {
+            check_json_cards(path, &white, "white", problems);
+            check_json_cards(path, &black, "black", problems);
+        },
+        Err(err) => {
+            let line = Some(err.line() as u64).filter(|x| *x != 0);
+            problems.push(Problem { file: path.to_path_buf(), line, message: err.to_string() });
+        },
+    }
+}
+
+fn check_json_cards<C: CheckedCard>(path: &Path, cards: &[C], kind: &str, problems: &mut Vec<Problem>) {
+    let mut ids = HashMap::new();
+    for (idx, card) in cards.iter().enumerate() {
+        let at = format!("{} card {}", kind, idx + 1);
+        for message in check_card(card, at.clone(), &mut ids) {
+            problems.push(Problem { file: path.to_path_buf(), line: None, message: format!("{}: {}", at, message) });
+        }
+    }
+}
+
+fn check_file<C: CheckedCard>(path: &Path, problems: &mut Vec<Problem>) {
+    match File::open(path) {
+        Ok(file) => check_csv::<C, _>(path, file, problems),
+        Err(err) => problems.push(Problem { file: path.to_path_buf(), line: None, message: err.to_string() }),
+    }
+}
+
+fn check_csv<C: CheckedCard, R: Read>(path: &Path, read: R, problems: &mut Vec<Problem>) {
+    let mut ids = HashMap::new();
+    let mut problem = |line: Option<u64>, message: String| problems.push(Problem { file: path.to_path_buf(), line, message });
+    let mut reader = Reader::from_reader(read);
+    let headers = match reader.headers() {
+        Ok(headers) => headers.clone(),
+        Err(err) => return problem(Some(1), err.to_string()),
+    };
+    for column in headers.iter().filter(|x| !C::COLUMNS.contains(x)) {
+        problem(Some(1), format!("unknown column '{}'", column));
+    }
+    let mut record = StringRecord::new();
+    loop {
+        match reader.read_record(&mut record) {
+            Ok(true) => {},
+            Ok(false) => break,
+            Err(err) => {
+                let io = err.is_io_error();
+                problem(Some(reader.position().line()), err.to_string());
+                if io {
+                    break;
+                }
+                continue;
+            }
+        }
+        let line = record.position().map(|x| x.line()).unwrap_or(0);
+        let card = match record.deserialize::<C>(Some(&headers)) {
+            Ok(card) => card,
+            Err(err) => {
+                problem(Some(line), err.to_string());
+                continue;
+            }
+        };
+        for message in check_card(&card, format!("line {}", line), &mut ids) {
+            problem(Some(line), message);
+        }
+    }
+}
+
+// the files a deck can be made of
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum DeckFile {
+    White,
+    Black,
+    Json,
+    Manifest,
+}
+
+impl DeckFile {
+    pub fn file_name(self) -> &'static str {
+        match self {
+            DeckFile::White => "white.csv",
+            DeckFile::Black => "black.csv",
+            DeckFile::Json => "cards.json",
+            DeckFile::Manifest => "deck.toml",
+        }
+    }
+    // works out what an uploaded file is from its extension, and for csvs whether it has a play column
+    pub fn identify(file_name: &str, contents: &[u8]) -> Option<DeckFile> {
+        let file_name = file_name.to_lowercase();
+        if file_name.ends_with(".json") {
+            Some(DeckFile::Json)
+        } else if file_name.ends_with(".toml") {
+            Some(DeckFile::Manifest)
+        } else if file_name.ends_with(".csv") {
+            let mut reader = Reader::from_reader(contents);
+            let black = reader.headers().ok()?.iter().any(|x| x == "play");
+            Some(if black { DeckFile::Black } else { DeckFile::White })
+        } else {
+            None
+        }
+    }
+}
+
+// the same checks check_decks does, for a file that hasn't been saved yet
+pub fn check_deck_file(kind: DeckFile, file_name: &str, contents: &[u8], guild: Option<GuildId>) -> Vec<Problem> {
+    let path = Path::new(file_name);
+    let mut problems = Vec::new();
+    match kind {
+        DeckFile::White => check_csv::<WhiteCardRecord, _>(path, contents, &mut problems),
+        DeckFile::Black => check_csv::<BlackCardRecord, _>(path, contents, &mut problems),
+        DeckFile::Json => check_json(path, contents, &mut problems),
+        DeckFile::Manifest => {
+            let manifest = str::from_utf8(contents).map_err(|x| x.to_string())
+                .and_then(|x| toml::from_str::<DeckManifest>(x).map_err(|x| x.to_string()));
+            match manifest {
+                Ok(manifest) => for dependency in manifest.dependencies {
+                    if let Err(err) = find_deck(&dependency, guild) {
+                        problems.push(Problem { file: path.to_path_buf(), line: None, message: err.to_string() });
+                    }
+                },
+                Err(message) => problems.push(Problem { file: path.to_path_buf(), line: None, message }),
+            }
+        },
+    }
+    problems
+}
+
+// writes the files into the guild's copy of the deck, keeping whichever ones weren't uploaded
+pub fn save_guild_deck(guild: GuildId, name: &str, files: &[(DeckFile, Vec<u8>)]) -> Result<(), IoError> {
+    let dir = guild_deck_root(guild).join(name);
+    fs::create_dir_all(&dir)?;
+    let uploaded = |kind| files.iter().any(|&(x, _)| x == kind);
+    // find_deck prefers the csvs, so a deck can't be both
+    let replaced: &[DeckFile] = if uploaded(DeckFile::Json) {
+        &[DeckFile::White, DeckFile::Black]
+    } else if uploaded(DeckFile::White) || uploaded(DeckFile::Black) {
+        &[DeckFile::Json]
+    } else {
+        &[]
+    };
+    for kind in replaced {
+        let path = dir.join(kind.file_name());
+        if path.exists() {
+            fs::remove_file(path)?;
+        }
+    }
+    for &(kind, ref contents) in files {
+        fs::write(dir.join(kind.file_name()), contents)?;
+    }
+    // a csv deck needs both files, even if it only has one colour of card
+    if !dir.join(DeckFile::Json.file_name()).exists() {
+        for &(kind, columns) in &[(DeckFile::White, WHITE_COLUMNS), (DeckFile::Black, BLACK_COLUMNS)] {
+            let path = dir.join(kind.file_name());
+            if !path.exists() {
+                fs::write(path, columns.join(",") + "\n")?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn read_records<C: DeserializeOwned>(path: &Path) -> Result<Vec<C>, IoError> {
+    let mut reader = Reader::from_path(path)?;
+    let mut vec = Vec::new();
+    for record in reader.deserialize() {
+        vec.push(record?);
+    }
+    Ok(vec)
+}
+
+fn write_records<C: CheckedCard + Serialize>(path: &Path, records: &[C]) -> Result<(), IoError> {
+    write_records_to(File::create(path)?, records)
+}
+
+fn write_records_to<C: CheckedCard + Serialize, W: Write>(write: W, records: &[C]) -> Result<(), IoError> {
+    // the header's written by hand so that empty files still get one
+    let mut writer = WriterBuilder::new().has_headers(false).from_writer(write);
+    writer.write_record(C::COLUMNS)?;
+    for record in records {
+        writer.serialize(record)?;
+    }
+    writer.flush()
+}
+
+// a guild deck opened for editing in chat. it's always saved as csvs, so json decks get converted
+pub struct DeckEditor {
+    dir: PathBuf,
+    json: Option<PathBuf>,
+    black: Vec<BlackCardRecord>,
+    white: Vec<WhiteCardRecord>,
+}
+
+impl DeckEditor {
+    pub fn open(guild: GuildId, name: &str) -> Result<DeckEditor, IoError> {
+        if !guild_decks(guild)?.iter().any(|x| x == name) {
+            return Err(IoError::new(ErrorKind::NotFound, format!("This server doesn't have a deck called {}", name)));
+        }
+        let (json, (black, white)) = match find_deck(name, Some(guild))? {
+            DeckSource::Csv(dir) => (None, (read_records(&dir.join("black.csv"))?, read_records(&dir.join("white.csv"))?)),
+            DeckSource::Json(path) => {
+                let records = parse_json_records(File::open(&path)?)?;
+                (Some(path), records)
+            },
+        };
+        Ok(DeckEditor { dir: guild_deck_root(guild).join(name), json, black, white })
+    }
+    // new cards are numbered after the highest explicit id, so they're short enough to type into `cah deck remove`
+    fn next_id(&self) -> u64 {
+        self.black.iter().filter_map(|x| x.id)
+            .chain(self.white.iter().filter_map(|x| x.id))
+            .max()
+            .map(|x| x + 1)
+            .unwrap_or(1)
+    }
+    fn check<C: CheckedCard>(card: &C) -> Result<(), IoError> {
+        match check_card(card, String::new(), &mut HashMap::new()).into_iter().next() {
+            Some(problem) => Err(IoError::new(ErrorKind::InvalidInput, problem)),
+            None => Ok(()),
+        }
+    }
+    pub fn add_white(&mut self, message: String) -> Result<u64, IoError> {
+        let card = WhiteCardRecord { id: Some(self.next_id()), message, tags: String::new() };
+        Self::check(&card)?;
+        let id = card.id();
+        self.white.push(card);
+        Ok(id)
+    }
+    pub fn add_black(&mut self, message: String, play: u8, draw: u8) -> Result<u64, IoError> {
+        let card = BlackCardRecord { id: Some(self.next_id()), draw, play, message, tags: String::new() };
+        Self::check(&card)?;
+        let id = card.id();
+        self.black.push(card);
+        Ok(id)
+    }
+    // how many cards had the id, at most one of each colour. `black` picks the colour when both do
+    pub fn remove(&mut self, id: u64, black: Option<bool>) -> usize {
+        let before = self.black.len() + self.white.len();
+        if black != Some(false) {
+            self.black.retain(|x| x.id() != id);
+        }
+        if black != Some(true) {
+            self.white.retain(|x| x.id() != id);
+        }
+        before - self.black.len() - self.white.len()
+    }
+    pub fn has_black(&self, id: u64) -> bool {
+        self.black.iter().any(|x| x.id() == id)
+    }
+    pub fn has_white(&self, id: u64) -> bool {
+        self.white.iter().any(|x| x.id() == id)
+    }
+    pub fn save(self) -> Result<(), IoError> {
+        fs::create_dir_all(&self.dir)?;
+        write_records(&self.dir.join(DeckFile::Black.file_name()), &self.black)?;
+        write_records(&self.dir.join(DeckFile::White.file_name()), &self.white)?;
+        if let Some(json) = self.json {
+            fs::remove_file(json)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn resolve(names: &[&str], graph: &[(&str, &[&str])]) -> Result<Vec<String>, IoError> {
+        let names = names.iter().map(|x| x.to_string()).collect::<Vec<_>>();
+        resolve_with(&names, |name| match graph.iter().find(|(deck, _)| *deck == name) {
+            Some((_, dependencies)) => Ok(dependencies.iter().map(|x| x.to_string()).collect()),
+            None => Err(IoError::new(ErrorKind::NotFound, format!("No deck {}", name))),
+        })
+    }
+
+    #[test]
+    fn dependencies_come_first_and_only_once() {
+        let graph: &[(&str, &[&str])] = &[("base", &[]), ("red", &["base"]), ("blue", &["base", "red"])];
+        assert_eq!(resolve(&["blue"], graph).unwrap(), vec!["base", "red", "blue"]);
+        assert_eq!(resolve(&["red", "blue", "base"], graph).unwrap(), vec!["base", "red", "blue"]);
+    }
+
+    #[test]
+    fn cycles_are_errors() {
+        let graph: &[(&str, &[&str])] = &[("a", &["b"]), ("b", &["c"]), ("c", &["a"])];
+        assert!(resolve(&["a"], graph).unwrap_err().to_string().contains("depends on itself"));
+        let graph: &[(&str, &[&str])] = &[("a", &["a"])];
+        assert!(resolve(&["a"], graph).is_err());
+    }
+
+    #[test]
+    fn shared_dependencies_are_not_cycles() {
+        let graph: &[(&str, &[&str])] = &[("base", &[]), ("a", &["base"]), ("b", &["base"]), ("both", &["a", "b"])];
+        assert_eq!(resolve(&["both"], graph).unwrap(), vec!["base", "a", "b", "both"]);
+    }
+
+    fn json(text: &str) -> (Vec<BlackCard>, Vec<WhiteCard>) {
+        parse_json_deck(text.as_bytes(), "test").unwrap()
+    }
+
+    #[test]
+    fn lone_underscores_become_blanks() {
+        assert_eq!(widen_blanks("I like _."), "I like ____.");
+        assert_eq!(widen_blanks("____ and _"), "____ and ____");
+        assert_eq!(widen_blanks("a __ b"), "a __ b");
+    }
+
+    #[test]
+    fn json_whole_text_cards() {
+        let (black, white) = json(r#"{"black": [{"text": "I like _.", "pick": 1}], "white": ["Cats."]}"#);
+        assert_eq!(black[0].message, "I like ____.");
+        assert_eq!(black[0].play, 1);
+        assert_eq!(white[0].message, "Cats.");
+    }
+
+    #[test]
+    fn json_parts_are_joined_with_blanks() {
+        let (black, white) = json(r#"{"calls": [{"text": ["Why ", " and ", "?"]}], "responses": [{"text": ["Dogs"]}]}"#);
+        assert_eq!(black[0].message, "Why ____ and ____?");
+        assert_eq!(black[0].play, 2);
+        assert_eq!(white[0].message, "Dogs");
+    }
+
+    #[test]
+    fn json_pack_lists_keep_ids_and_tags() {
+        let (black, white) = json(r#"[
+            {"black": [{"text": "_ + _ = _", "pick": 3, "draw": 2, "id": 5, "tags": ["Maths"]}, "What's that?"]},
+            {"white": [{"text": "One", "id": 9}]}
+        ]"#);
+        assert_eq!(black[0].message, "____ + ____ = ____");
+        assert_eq!((black[0].play, black[0].draw), (3, 2));
+        assert_eq!(black[0].id, BlackCardId::new("test", 5));
+        assert_eq!(black[0].tags, vec!["maths".to_string()]);
+        // no blanks and no pick is still one answer
+        assert_eq!(black[1].play, 1);
+        assert_eq!(white[0].id, WhiteCardId::new("test", 9));
+    }
+
+    #[test]
+    fn missing_dependencies_are_errors() {
+        let graph: &[(&str, &[&str])] = &[("a", &["gone"])];
+        assert_eq!(resolve(&["a"], graph).unwrap_err().kind(), ErrorKind::NotFound);
+    }
+}
@@ -0,0 +1,322 @@
+use serenity::Result as SerenityResult;
+use serenity::builder::CreateEmbed;
+use serenity::model::channel::Message;
+use serenity::model::id::{ChannelId, MessageId};
+use serenity::utils::Colour;
+use std::cmp;
+use std::fmt::Write;
+use std::mem;
+use std::ops::Range;
+
+// the most Discord will take in one message
+pub const MESSAGE_LIMIT: usize = 2000;
+// and in an embed: fields, a field's value, the description, and everything put together
+const MAX_FIELDS: usize = 25;
+const FIELD_LIMIT: usize = 1024;
+const DESCRIPTION_LIMIT: usize = 2048;
+const EMBED_LIMIT: usize = 6000;
+
+// not quite black, since Discord takes 0 to mean no colour at all
+pub fn black_card_colour() -> Colour {
+    Colour::from_rgb(17, 17, 17)
+}
+
+pub fn white_card_colour() -> Colour {
+    Colour::from_rgb(245, 245, 245)
+}
+
+// card text and anything else people wrote, made safe to send: markdown shows up as written and mentions don't ping.
+// anything that should be bold is bolded after escaping
+pub fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\\' | '*' | '_' | '~' | '`' | '|' | '>' => {
+                escaped.push('\\');
+                escaped.push(c);
+            },
+            _ => escaped.push(c),
+        }
+    }
+    escape_mentions(&escaped)
+}
+
+// a zero width space after every @ stops @everyone, @here and <@id> from pinging anyone
+pub fn escape_mentions(text: &str) -> String {
+    text.replace('@', "@\u{200B}")
+}
+
+// a black card, a hand, or the submissions: a title, some details, a numbered list, and the round and scores underneath.
+// sent as an embed unless the config asks for plain text
+pub struct CardMessage {
+    pub colour: Colour,
+    pub title: String,
+    pub description: String,
+    pub items: Vec<String>,
+    pub footer: Option<String>,
+}
+
+impl CardMessage {
+    pub fn new(colour: Colour, title: String) -> Self {
+        CardMessage { colour, title, description: String::new(), items: Vec::new(), footer: None }
+    }
+    // the way everything was sent before embeds. the footer's left off, it's in the other messages anyway
+    pub fn to_text(&self) -> String {
+        let mut string = self.title.clone();
+        if !self.description.is_empty() {
+            string.push('\n');
+            string.push_str(&self.description);
+        }
+        if !self.items.is_empty() {
+            string.push_str("\n\n");
+            for (idx, item) in self.items.iter().enumerate() {
+                let _ = write!(&mut string, "{}: {}\n", idx, item);
+            }
+            string.pop();
+        }
+        string
+    }
+    // whether it can go in embeds at all, given as many as it takes for the items
+    fn fits(&self) -> bool {
+        let footer = self.footer.as_ref().map(|x| x.len()).unwrap_or(0);
+        self.title.len() <= 256 && self.description.len() <= DESCRIPTION_LIMIT
+            && self.title.len() + self.description.len() + footer < EMBED_LIMIT
+            && self.items.iter().all(|x| x.len() <= FIELD_LIMIT)
+    }
+    // one embed's worth: the title and description go on the first, the footer on the last
+    fn page(&self, mut e: CreateEmbed, items: Range<usize>, first: bool, last: bool) -> CreateEmbed {
+        e = e.colour(self.colour);
+        if first {
+            e = e.title(&self.title);
+            if !self.description.is_empty() {
+                e = e.description(&self.description);
+            }
+        }
+        for idx in items {
+            e = e.field(idx.to_string(), self.items[idx].as_str(), false);
+        }
+        if last {
+            if let Some(ref footer) = self.footer {
+                e = e.footer(|f| f.text(footer));
+            }
+        }
+        e
+    }
+    // which items go in each embed, keeping under the field count and the size limit
+    fn pages(&self) -> Vec<Range<usize>> {
+        let footer = self.footer.as_ref().map(|x| x.len()).unwrap_or(0);
+        let mut pages = vec![0..0];
+        let mut size = self.title.len() + self.description.len() + footer;
+        for (idx, item) in self.items.iter().enumerate() {
+            let length = item.len() + idx.to_string().len();
+            let page = pages.last_mut().unwrap();
+            if page.len() == MAX_FIELDS || size + length > EMBED_LIMIT {
+                pages.push(idx..idx + 1);
+                size = self.title.len() + footer + length;
+            } else {
+                page.end += 1;
+                size += length;
+            }
+        }
+        pages
+    }
+    // as many embeds as it takes to fit the items, with the footer on the last one. returns the last message sent
+    pub fn send(&self, channel: ChannelId, plain_text: bool) -> SerenityResult<Option<Message>> {
+        if plain_text || !self.fits() {
+            let mut sent = None;
+            for chunk in split_message(&self.to_text()) {
+                sent = Some(channel.say(chunk)?);
+            }
+            return Ok(sent);
+        }
+        let pages = self.pages();
+        let last = pages.len() - 1;
+        let mut sent = None;
+        for (number, page) in pages.into_iter().enumerate() {
+            sent = Some(channel.send_message(|m| m.embed(|e| self.page(e, page, number == 0, number == last)))?);
+        }
+        Ok(sent)
+    }
+    // replaces a message sent with send. only for ones that take a single message, anything past that is cut off
+    pub fn edit(&self, channel: ChannelId, message: MessageId, plain_text: bool) -> SerenityResult<()> {
+        if plain_text || !self.fits() {
+            let text = split_message(&self.to_text()).into_iter().next().unwrap_or_default();
+            channel.edit_message(message, |m| m.content(text))?;
+        } else {
+            let items = 0..cmp::min(self.items.len(), MAX_FIELDS);
+            channel.edit_message(message, |m| m.embed(|e| self.page(e, items, true, true)))?;
+        }
+        Ok(())
+    }
+}
+
+// keycaps for 0 to 9, and then 10. the submissions are numbered from 0
+pub fn number_emoji(number: usize) -> Option<String> {
+    match number {
+        0..=9 => Some(format!("{}\u{FE0F}\u{20E3}", number)),
+        10 => Some("\u{1F51F}".to_string()),
+        _ => None,
+    }
+}
+
+pub fn emoji_number(emoji: &str) -> Option<usize> {
+    (0..11).find(|x| number_emoji(*x).map(|y| y == emoji || y.replace('\u{FE0F}', "") == emoji).unwrap_or(false))
+}
+
+// sends text of any length, as a few messages if it has to
+pub fn say(channel: ChannelId, text: &str) -> SerenityResult<()> {
+    for chunk in split_message(text) {
+        channel.say(chunk)?;
+    }
+    Ok(())
+}
+
+// breaks text into pieces that each fit in a message, between lines where it can, then between words
+pub fn split_message(text: &str) -> Vec<String> {
+    let mut chunks = Vec::new();
+    let mut chunk = String::new();
+    // whether the chunk has a line in it yet, which might be an empty one
+    let mut started = false;
+    for line in text.split('\n') {
+        if started && chunk.len() + 1 + line.len() > MESSAGE_LIMIT {
+            chunks.push(mem::replace(&mut chunk, String::new()));
+            started = false;
+        }
+        if started {
+            chunk.push('\n');
+        }
+        let mut line = line;
+        while line.len() > MESSAGE_LIMIT {
+            let (head, tail) = line.split_at(split_point(line));
+            chunks.push(head.to_string());
+            line = tail.trim_left();
+        }
+        chunk.push_str(line);
+        started = true;
+    }
+    chunks.push(chunk);
+    // Discord won't send an empty message
+    chunks.retain(|x| !x.trim().is_empty());
+    chunks
+}
+
+// where to cut a line that's too long for one message: the last space that fits, or just the last character that does.
+// either way, not in the middle of an escape or a bold answer
+fn split_point(line: &str) -> usize {
+    let mut end = MESSAGE_LIMIT;
+    while !line.is_char_boundary(end) {
+        end -= 1;
+    }
+    if let Some(idx) = line[..end].rfind(' ').filter(|x| *x > 0) {
+        end = idx;
+    }
+    // a bold answer goes on to the next message whole, unless the line starts with it and there is no helping it
+    if let Some(idx) = open_bold(&line[..end]).filter(|x| *x > 0) {
+        end = idx;
+    }
+    if end > 1 && line[..end].chars().rev().take_while(|x| *x == '\\').count() % 2 == 1 {
+        end -= 1;
+    }
+    end
+}
+
+// where the last ** that isn't closed by the end of the text is. escaped *s don't count
+fn open_bold(text: &str) -> Option<usize> {
+    let bytes = text.as_bytes();
+    let mut open = None;
+    let mut idx = 0;
+    while idx < bytes.len() {
+        match bytes[idx] {
+            b'\\' => idx += 2,
+            b'*' if bytes.get(idx + 1) == Some(&b'*') => {
+                open = if open.is_some() { None } else { Some(idx) };
+                idx += 2;
+            },
+            _ => idx += 1,
+        }
+    }
+    open
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(items: Vec<String>) -> CardMessage {
+        let mut message = CardMessage::new(black_card_colour(), "Title".to_string());
+        message.items = items;
+        message
+    }
+
+    #[test]
+    fn short_text_is_one_message() {
+        assert_eq!(split_message("one\ntwo"), vec!["one\ntwo".to_string()]);
+    }
+
+    #[test]
+    fn empty_text_sends_nothing() {
+        assert!(split_message("").is_empty());
+        assert!(split_message(" \n ").is_empty());
+    }
+
+    #[test]
+    fn splits_between_lines() {
+        let line = "a".repeat(1500);
+        let text = format!("{}\n{}", line, line);
+        assert_eq!(split_message(&text), vec![line.clone(), line]);
+    }
+
+    #[test]
+    fn splits_long_lines_between_words() {
+        let word = "a".repeat(999);
+        let text = format!("{} {} {}", word, word, word);
+        let chunks = split_message(&text);
+        assert_eq!(chunks, vec![format!("{} {}", word, word), word]);
+    }
+
+    #[test]
+    fn splits_on_char_boundaries() {
+        let text = "é".repeat(MESSAGE_LIMIT);
+        let chunks = split_message(&text);
+        assert!(chunks.iter().all(|x| x.len() <= MESSAGE_LIMIT));
+        assert_eq!(chunks.concat(), text);
+    }
+
+    #[test]
+    fn escapes_stay_with_what_they_escape() {
+        let text = format!("{}\\_b", "a".repeat(MESSAGE_LIMIT - 1));
+        let chunks = split_message(&text);
+        assert_eq!(chunks, vec!["a".repeat(MESSAGE_LIMIT - 1), "\\_b".to_string()]);
+        // an escaped backslash is already whole
+        let text = format!("{}\\\\b", "a".repeat(MESSAGE_LIMIT - 2));
+        assert_eq!(split_message(&text)[0], format!("{}\\\\", "a".repeat(MESSAGE_LIMIT - 2)));
+    }
+
+    #[test]
+    fn bold_answers_stay_in_one_piece() {
+        let text = format!("{}**bold answer here** end", "a ".repeat(990));
+        let chunks = split_message(&text);
+        assert!(!chunks[0].contains("**"));
+        assert!(chunks[1].starts_with("**bold answer here**"));
+        // escaped stars aren't bold
+        assert_eq!(open_bold("\\*\\* and **this"), Some(9));
+        assert_eq!(open_bold("**done** \\**"), None);
+    }
+
+    #[test]
+    fn one_page_when_it_fits() {
+        assert_eq!(message(vec!["card".to_string(); 10]).pages(), vec![0..10]);
+        assert_eq!(message(Vec::new()).pages(), vec![0..0]);
+    }
+
+    #[test]
+    fn pages_at_the_field_count() {
+        assert_eq!(message(vec!["card".to_string(); 30]).pages(), vec![0..MAX_FIELDS, MAX_FIELDS..30]);
+    }
+
+    #[test]
+    fn pages_at_the_size_limit() {
+        // each item is 1001 with its number, so five fit with the title and the sixth doesn't
+        assert_eq!(message(vec!["a".repeat(1000); 7]).pages(), vec![0..5, 5..7]);
+    }
+}
@@ -0,0 +1,121 @@
+use serde_json;
+use serenity::model::id::GuildId;
+use std::fmt::Write;
+use std::io::{Error as IoError, ErrorKind};
+use std::str::FromStr;
+use super::{BlackCard, WhiteCard, deck};
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum ExportFormat {
+    Csv,
+    Json,
+    // a printable sheet of cards
+    Html,
+}
+
+impl FromStr for ExportFormat {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "csv" => Ok(ExportFormat::Csv),
+            "json" => Ok(ExportFormat::Json),
+            "html" => Ok(ExportFormat::Html),
+            _ => Err(format!("Unknown format '{}' (try 'csv', 'json' or 'html')", s)),
+        }
+    }
+}
+
+// the same shape load_deck reads back in
+#[derive(Serialize)]
+struct JsonExport<'a> {
+    black: Vec<JsonExportCard<'a>>,
+    white: Vec<JsonExportCard<'a>>,
+}
+
+#[derive(Serialize)]
+struct JsonExportCard<'a> {
+    text: &'a str,
+    id: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pick: Option<u8>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    draw: Option<u8>,
+    #[serde(skip_serializing_if = "no_tags")]
+    tags: &'a [String],
+}
+
+fn no_tags(tags: &&[String]) -> bool {
+    tags.is_empty()
+}
+
+// file name and contents of every file the deck exports to
+pub fn export_deck(name: &str, guild: Option<GuildId>, format: ExportFormat) -> Result<Vec<(String, Vec<u8>)>, IoError> {
+    let (black, white) = deck::load_deck(name, guild)?;
+    match format {
+        ExportFormat::Csv => {
+            let mut white_csv = Vec::new();
+            deck::write_white_cards(&mut white_csv, &white)?;
+            let mut black_csv = Vec::new();
+            deck::write_black_cards(&mut black_csv, &black)?;
+            Ok(vec![(format!("{}-white.csv", name), white_csv), (format!("{}-black.csv", name), black_csv)])
+        },
+        ExportFormat::Json => {
+            let export = JsonExport {
+                black: black.iter().map(|x| JsonExportCard { text: &x.message, id: x.id.number(), pick: Some(x.play), draw: Some(x.draw), tags: &x.tags }).collect(),
+                white: white.iter().map(|x| JsonExportCard { text: &x.message, id: x.id.number(), pick: None, draw: None, tags: &x.tags }).collect(),
+            };
+            let json = serde_json::to_vec_pretty(&export).map_err(|x| IoError::new(ErrorKind::InvalidData, x))?;
+            Ok(vec![(format!("{}.json", name), json)])
+        },
+        ExportFormat::Html => {
+            let title = deck::load_manifest(name, guild)?.name.unwrap_or_else(|| name.to_string());
+            Ok(vec![(format!("{}.html", name), print_sheet(&title, &black, &white).into_bytes())])
+        },
+    }
+}
+
+fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+// poker-sized cards, as many to a page as fit, to print and cut out
+const PRINT_STYLE: &str = "\
+@page { margin: 0.5in; }
+body { margin: 0; font-family: Helvetica, Arial, sans-serif; }
+.sheet { display: flex; flex-wrap: wrap; }
+.card { box-sizing: border-box; width: 2.5in; height: 3.5in; padding: 0.2in; border: 1px solid #000; position: relative; page-break-inside: avoid; break-inside: avoid; font-size: 14pt; font-weight: bold; line-height: 1.2; }
+.black { background: #000; color: #fff; -webkit-print-color-adjust: exact; print-color-adjust: exact; }
+.white { background: #fff; color: #000; }
+.footer { position: absolute; left: 0.2in; right: 0.2in; bottom: 0.15in; font-size: 7pt; font-weight: normal; }
+.pick { float: right; }
+";
+
+fn print_sheet(title: &str, black: &[BlackCard], white: &[WhiteCard]) -> String {
+    let title = escape_html(title);
+    let mut html = String::new();
+    // writing to a String can't fail
+    let _ = write!(&mut html, "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n<style>\n{}</style>\n</head>\n<body>\n<div class=\"sheet\">\n", title, PRINT_STYLE);
+    for card in black {
+        let mut pick = format!("Pick {}", card.play);
+        if card.draw > 0 {
+            pick = format!("Draw {}, {}", card.draw, pick);
+        }
+        let _ = write!(&mut html, "<div class=\"card black\">{}<div class=\"footer\">{}<span class=\"pick\">{}</span></div></div>\n", escape_html(&card.message), title, pick);
+    }
+    for card in white {
+        let _ = write!(&mut html, "<div class=\"card white\">{}<div class=\"footer\">{}</div></div>\n", escape_html(&card.message), title);
+    }
+    html.push_str("</div>\n</body>\n</html>\n");
+    html
+}
@@ -1,5 +1,3 @@
-use csv::Reader;
-use csv::Error as CsvError;
 use rand::Rng;
 use serde::{Serialize, Deserialize};
 use serenity::Result as SerenityResult;
@@ -8,33 +6,85 @@ use serenity::framework::standard::{Args, CommandError};
 use serenity::model::channel::Message;
 use serenity::model::id::UserId;
 use smallvec::SmallVec;
-use std::collections::HashMap;
-use std::io::Read;
+use std::collections::{HashMap, HashSet};
 use typemap::Key;
-use std::fs::File;
-use std::path::Path;
-use std::io::Error as IoError;
+use std::str::FromStr;
+use std::fmt::{Display, Formatter, Result as FmtResult};
 use std::collections::VecDeque;
-use serenity::model::id::ChannelId;
+use std::time::{Duration, SystemTime};
+use serenity::model::id::{ChannelId, GuildId, MessageId};
 
+pub mod bans;
 pub mod commands;
+pub mod deck;
+pub mod display;
+pub mod export;
+pub mod render;
+pub mod search;
+
+pub use self::deck::{load_deck, parse_white_cards, parse_black_cards};
+use self::bans::BanList;
+use self::render::CardRenderer;
 
 // One game of CAH. Not compatible with multiple instances. May change.
 pub struct CahManager {
     hands: HashMap<UserId, Vec<WhiteCardId>>,
-    wins: HashMap<UserId, Vec<BlackCardId>>,
-    in_play: HashMap<UserId, Vec<WhiteCardId>>,
+    // each black card won and the points that came with it
+    wins: HashMap<UserId, Vec<(BlackCardId, u32)>>,
+    scores: HashMap<UserId, u32>,
+    // one entry per answer; only gamblers get more than one
+    in_play: HashMap<UserId, Vec<Vec<WhiteCardId>>>,
+    wagers: HashMap<UserId, (BlackCardId, u32)>,
     state: State,
     black_deck: HashMap<BlackCardId, BlackCard>,
     white_deck: HashMap<WhiteCardId, WhiteCard>,
+    // answers written on blank cards; they're only around for the one game
+    write_ins: HashMap<WhiteCardId, WhiteCard>,
+    next_write_in: u64,
+    // a black card put into play from outside the decks, like the haiku. kept apart so it never ends up in the piles
+    injected_black_card: Option<BlackCard>,
     black_deck_state: VecDeque<BlackCardId>,
     white_deck_state: VecDeque<WhiteCardId>,
     deck_names: Vec<String>,
+    // how much of the piles each deck should make up, relative to the others
+    deck_weights: HashMap<DeckKey, u32>,
+    tag_filter: TagFilter,
+    // taken out mid-game. their cards stay playable until the next game, so that nobody's hand changes under them
+    removed_decks: HashSet<DeckKey>,
+    // left out of this game only, because the channel isn't NSFW. the decks themselves stay as they were set
+    excluded_decks: HashSet<DeckKey>,
+    // for whichever guild the game is in
+    bans: BanList,
     hand_size: u8,
-    win_condition: u8,
+    blank_cards: u8,
+    win_condition: u32,
+    house_rules: HashSet<HouseRule>,
+    mode: GameMode,
+    judging: Judging,
+    sfw_policy: SfwPolicy,
+    // None sends cards as text only
+    renderer: Option<CardRenderer>,
+    // embeds are used unless this is set
+    plain_text: bool,
+    round: u32,
+    // None leaves a round open until everyone's played
+    round_time_limit: Option<Duration>,
+    round_deadline: Option<SystemTime>,
+    // the board for the current round, edited as people play
+    status_message: Option<MessageId>,
+    // shuffled once when the answers are read out, so the numbers stay put while people pick and vote
+    submissions: Vec<(UserId, SmallVec<[WhiteCardId; 5]>)>,
+    // voter -> index into get_submissions()
+    votes: HashMap<UserId, usize>,
+    // the message the submissions were posted in, which the czar and voters can react to
+    submissions_message: Option<MessageId>,
+    // the czar's picks so far, when there's more than one to make by reacting
+    reaction_picks: SmallVec<[usize; 3]>,
     players: PlayerContainer,
     primary_channel: ChannelId,
+    guild: Option<GuildId>,
     current_black_card: Option<BlackCardId>,
+    final_round: bool,
 }
 
 impl CahManager {
@@ -47,18 +97,45 @@ impl CahManager {
         CahManager {
             hands: HashMap::new(),
             wins: HashMap::new(),
+            scores: HashMap::new(),
             state: State::Off,
             black_deck,
             white_deck,
+            write_ins: HashMap::new(),
+            next_write_in: 0,
             deck_names,
+            deck_weights: HashMap::new(),
+            tag_filter: TagFilter::default(),
+            removed_decks: HashSet::new(),
+            excluded_decks: HashSet::new(),
+            bans: BanList::default(),
+            injected_black_card: None,
             black_deck_state,
             white_deck_state,
             hand_size: 10,
+            blank_cards: 0,
             in_play: HashMap::new(),
+            wagers: HashMap::new(),
             win_condition: 4,
+            house_rules: HashSet::new(),
+            mode: GameMode::Cards,
+            judging: Judging::Czar,
+            sfw_policy: SfwPolicy::Refuse,
+            renderer: None,
+            plain_text: false,
+            round: 0,
+            round_time_limit: None,
+            round_deadline: None,
+            status_message: None,
+            submissions: Vec::new(),
+            votes: HashMap::new(),
+            submissions_message: None,
+            reaction_picks: SmallVec::new(),
             players: PlayerContainer::new(),
             primary_channel: ChannelId::default(),
+            guild: None,
             current_black_card: None,
+            final_round: false,
         }
     }
     pub fn is_running(&self) -> bool {
@@ -71,12 +148,26 @@ impl CahManager {
             // init game
             self.state = State::Waiting;
             self.wins.clear();
+            self.scores.clear();
             self.hands.clear();
-            self.black_deck_state.clear();
-            self.black_deck_state.extend(self.black_deck.keys().cloned());
-            self.white_deck_state.clear();
-            self.white_deck_state.extend(self.white_deck.keys().cloned());
+            let removed_decks = &self.removed_decks;
+            self.white_deck.retain(|id, _| !removed_decks.contains(&id.deck()));
+            self.black_deck.retain(|id, _| !removed_decks.contains(&id.deck()));
+            self.removed_decks.clear();
+            self.black_deck_state = self.black_pile();
+            // the blank card count might've changed since the decks were set
+            self.white_deck.retain(|_, x| !x.blank);
+            let blanks = self.make_blanks(&self.deck_names);
+            self.white_deck.extend(blanks.into_iter().map(|x| (x.id, x)));
+            self.write_ins.clear();
+            self.injected_black_card = None;
+            self.white_deck_state = self.white_pile();
             self.in_play.clear();
+            self.wagers.clear();
+            self.votes.clear();
+            self.final_round = false;
+            self.round = 0;
+            self.status_message = None;
             true
         }
     }
@@ -86,15 +177,26 @@ impl CahManager {
     pub fn get_primary_channel(&self) -> ChannelId {
         self.primary_channel
     }
+    pub fn set_guild(&mut self, guild: Option<GuildId>) {
+        self.guild = guild;
+    }
+    pub fn get_guild(&self) -> Option<GuildId> {
+        self.guild
+    }
     pub fn get_hand(&mut self, id: UserId) -> &mut Vec<WhiteCardId> {
         if self.hands.contains_key(&id) {
             // if they've got a hand, return it
             self.hands.get_mut(&id).unwrap()
         } else {
             // otherwise, let's make one. this is also the function to add new users to the game.
-            let mut hand = Vec::with_capacity(self.hand_size as usize);
-            for _ in 0..self.hand_size {
-                hand.push(self.draw_white());
+            let hand_size = if self.mode == GameMode::FreeText { 0 } else { self.hand_size };
+            let mut hand = Vec::with_capacity(hand_size as usize);
+            // a short hand if the pile runs out
+            for _ in 0..hand_size {
+                match self.draw_white() {
+                    Some(card) => hand.push(card),
+                    None => break,
+                }
             }
             self.hands.insert(id, hand);
             self.players.add_player(id);
@@ -102,42 +204,186 @@ impl CahManager {
         }
     }
     pub fn get_white_card(&self, id: WhiteCardId) -> Option<&WhiteCard> {
-        self.white_deck.get(&id)
+        self.white_deck.get(&id).or_else(|| self.write_ins.get(&id))
+    }
+    // turns what someone wrote on a blank card into a card of its own
+    pub fn write_in(&mut self, message: String) -> WhiteCardId {
+        let id = WhiteCardId::new(WRITE_IN_DECK, self.next_write_in);
+        self.next_write_in += 1;
+        self.write_ins.insert(id, WhiteCard { message, id, blank: false, tags: Vec::new() });
+        id
     }
     pub fn get_black_card(&self, id: BlackCardId) -> Option<&BlackCard> {
-        self.black_deck.get(&id)
+        self.black_deck.get(&id).or_else(|| self.injected_black_card.as_ref().filter(|x| x.id == id))
     }
     pub fn get_deck_names(&self) -> &[String] {
         &self.deck_names
     }
-    pub fn set_decks(&mut self, black_deck: Vec<BlackCard>, white_deck: Vec<WhiteCard>, deck_names: Vec<String>) {
-        self.white_deck_state.clear();
-        {
-            let to_check = self.hands.values().flat_map(|x| x.iter());
-            for card in white_deck.iter().map(|x| x.id) {
-                // don't include any white cards in hands
-                if !self.hands.values().any(|x| x.iter().any(|y| *y == card)) {
-                    self.white_deck_state.push_front(card);
-                }
-            }
-        }
-        self.black_deck_state.clear();
-        {
-            let to_check = self.wins.values().flat_map(|x| x.iter());
-            for card in black_deck.iter().map(|x| x.id) {
-                if !self.wins.values().any(|x| x.iter().any(|y| *y == card)) {
-                    self.black_deck_state.push_front(card);
-                }
-            }
-        }
-        let mut rng = rand::thread_rng();
-        rng.shuffle_deque(&mut self.white_deck_state);
-        rng.shuffle_deque(&mut self.black_deck_state);
+    pub fn set_decks(&mut self, black_deck: Vec<BlackCard>, mut white_deck: Vec<WhiteCard>, deck_names: Vec<String>) {
+        white_deck.extend(self.make_blanks(&deck_names));
         let black_deck_map = black_deck.into_iter().map(|x| (x.id, x)).collect::<HashMap<_, _>>();
         let white_deck_map = white_deck.into_iter().map(|x| (x.id, x)).collect::<HashMap<_, _>>();
         // explicitly not checking user hands because if they're replaced without the user checking their hands, they'll unintentionally play cards
         self.white_deck = white_deck_map;
         self.black_deck = black_deck_map;
+        self.deck_names = deck_names;
+        self.removed_decks.clear();
+        // don't include any white cards in hands, or black cards someone's won
+        let mut white_deck_state = self.white_pile();
+        white_deck_state.retain(|card| !self.hands.values().any(|x| x.contains(card)));
+        self.white_deck_state = white_deck_state;
+        let mut black_deck_state = self.black_pile();
+        black_deck_state.retain(|card| !self.wins.values().any(|x| x.iter().any(|(won, _)| won == card)));
+        self.black_deck_state = black_deck_state;
+    }
+    // shuffles a deck into what's left of the piles, leaving everything already drawn alone
+    pub fn add_deck(&mut self, black_deck: Vec<BlackCard>, mut white_deck: Vec<WhiteCard>, name: String) {
+        white_deck.extend(self.make_blanks(&[name.clone()]));
+        let key = DeckKey::of(&name);
+        self.removed_decks.remove(&key);
+        if !self.deck_names.contains(&name) {
+            self.deck_names.push(name);
+        }
+        let mut rng = rand::thread_rng();
+        for card in white_deck {
+            let id = card.id;
+            let allowed = white_allowed(&card, &self.tag_filter, &self.bans);
+            self.white_deck.insert(id, card);
+            let drawn = self.white_deck_state.contains(&id) || self.hands.values().any(|x| x.contains(&id)) || self.in_play.values().flat_map(|x| x.iter()).any(|x| x.contains(&id));
+            if allowed && !drawn {
+                let idx = rng.gen_range(0, self.white_deck_state.len() + 1);
+                self.white_deck_state.insert(idx, id);
+            }
+        }
+        for card in black_deck {
+            let id = card.id;
+            let allowed = black_allowed(&card, &self.tag_filter, &self.bans);
+            self.black_deck.insert(id, card);
+            let drawn = self.black_deck_state.contains(&id) || self.wins.values().any(|x| x.iter().any(|(won, _)| *won == id)) || self.current_black_card == Some(id);
+            if allowed && !drawn {
+                let idx = rng.gen_range(0, self.black_deck_state.len() + 1);
+                self.black_deck_state.insert(idx, id);
+            }
+        }
+    }
+    // takes a deck's cards out of the piles. anything already drawn stays where it is
+    pub fn remove_deck(&mut self, name: &str) -> bool {
+        let len = self.deck_names.len();
+        self.deck_names.retain(|x| x != name);
+        if self.deck_names.len() == len {
+            return false;
+        }
+        let key = DeckKey::of(name);
+        self.removed_decks.insert(key);
+        self.white_deck_state.retain(|x| x.deck() != key);
+        self.black_deck_state.retain(|x| x.deck() != key);
+        true
+    }
+    pub fn is_deck_removed(&self, deck: DeckKey) -> bool {
+        self.removed_decks.contains(&deck)
+    }
+    fn is_deck_in_play(&self, deck: DeckKey) -> bool {
+        !self.removed_decks.contains(&deck) && !self.excluded_decks.contains(&deck)
+    }
+    // set before new_game, which makes the piles without them
+    pub fn set_excluded_decks(&mut self, decks: HashSet<DeckKey>) {
+        self.excluded_decks = decks;
+    }
+    // every card that can be drawn, shuffled, without the banned ones and cut down to the deck weights
+    fn white_pile(&self) -> VecDeque<WhiteCardId> {
+        let mut pile = self.white_deck.values()
+            .filter(|x| self.is_deck_in_play(x.id.deck()))
+            .filter(|x| white_allowed(x, &self.tag_filter, &self.bans))
+            .map(|x| x.id)
+            .collect::<VecDeque<_>>();
+        rand::thread_rng().shuffle_deque(&mut pile);
+        self.apply_weights(&mut pile, |x: &WhiteCardId| x.deck());
+        pile
+    }
+    fn black_pile(&self) -> VecDeque<BlackCardId> {
+        let mut pile = self.black_deck.values()
+            .filter(|x| self.is_deck_in_play(x.id.deck()))
+            .filter(|x| black_allowed(x, &self.tag_filter, &self.bans))
+            .map(|x| x.id)
+            .collect::<VecDeque<_>>();
+        rand::thread_rng().shuffle_deque(&mut pile);
+        self.apply_weights(&mut pile, |x: &BlackCardId| x.deck());
+        pile
+    }
+    // cuts each weighted deck's share of a shuffled pile down so they come out in proportion. set-decks weights every deck or none
+    fn apply_weights<T>(&self, pile: &mut VecDeque<T>, deck: impl Fn(&T) -> DeckKey) {
+        if self.deck_weights.is_empty() {
+            return;
+        }
+        let mut counts = HashMap::new();
+        for key in pile.iter().map(&deck).filter(|x| self.deck_weights.contains_key(x)) {
+            *counts.entry(key).or_insert(0usize) += 1;
+        }
+        let total_weight = counts.keys().map(|x| self.deck_weights[x]).sum::<u32>() as f64;
+        // the biggest pile every deck can fill its share of
+        let size = counts.iter()
+            .map(|(key, count)| *count as f64 * total_weight / self.deck_weights[key] as f64)
+            .fold(f64::INFINITY, f64::min);
+        let mut left = counts.keys()
+            .map(|x| (*x, (size * self.deck_weights[x] as f64 / total_weight).round() as usize))
+            .collect::<HashMap<_, _>>();
+        pile.retain(|x| match left.get_mut(&deck(x)) {
+            Some(left) if *left == 0 => false,
+            Some(left) => {
+                *left -= 1;
+                true
+            },
+            None => true,
+        });
+    }
+    pub fn get_tag_filter(&self) -> &TagFilter {
+        &self.tag_filter
+    }
+    // takes effect the next time the piles are made
+    pub fn set_tag_filter(&mut self, tag_filter: TagFilter) {
+        self.tag_filter = tag_filter;
+    }
+    pub fn get_deck_weights(&self) -> &HashMap<DeckKey, u32> {
+        &self.deck_weights
+    }
+    // takes effect the next time the piles are made
+    pub fn set_deck_weights(&mut self, deck_weights: HashMap<DeckKey, u32>) {
+        self.deck_weights = deck_weights;
+    }
+    // how many black and white cards the piles would have to draw from with these bans
+    pub fn pile_sizes(&self, bans: &BanList) -> (usize, usize) {
+        let black = self.black_deck.values()
+            .filter(|x| self.is_deck_in_play(x.id.deck()) && black_allowed(x, &self.tag_filter, bans))
+            .count();
+        let white = self.white_deck.values()
+            .filter(|x| self.is_deck_in_play(x.id.deck()) && white_allowed(x, &self.tag_filter, bans))
+            .count();
+        (black, white)
+    }
+    pub fn get_bans(&self) -> &BanList {
+        &self.bans
+    }
+    // takes banned cards out of the draw piles straight away, but leaves any already in hands
+    pub fn set_bans(&mut self, bans: BanList) {
+        {
+            let (white_deck, black_deck) = (&self.white_deck, &self.black_deck);
+            self.white_deck_state.retain(|x| white_deck.get(x).map(|x| !bans.bans_white(x)).unwrap_or(true));
+            self.black_deck_state.retain(|x| black_deck.get(x).map(|x| !bans.bans_black(x)).unwrap_or(true));
+        }
+        self.bans = bans;
+    }
+    pub fn get_blank_cards(&self) -> u8 {
+        self.blank_cards
+    }
+    // per deck, takes effect the next time the decks are set or a game starts
+    pub fn set_blank_cards(&mut self, blank_cards: u8) {
+        self.blank_cards = blank_cards;
+    }
+    fn make_blanks(&self, decks: &[String]) -> Vec<WhiteCard> {
+        decks.iter()
+            .flat_map(|deck| (0..self.blank_cards as u64).map(move |x| WhiteCardId::new(deck, BLANK_ID_START + x)))
+            .map(|id| WhiteCard { message: BLANK_CARD_TEXT.to_string(), id, blank: true, tags: Vec::new() })
+            .collect()
     }
     pub fn get_hand_size(&self) -> u8 {
         self.hand_size
@@ -145,10 +391,10 @@ impl CahManager {
     pub fn set_hand_size(&mut self, hand_size: u8) {
         self.hand_size = hand_size;
     }
-    // mechanism for getting nonexistent cards and updating the hand simultaneously
-    pub fn resolve_white_card(&mut self, id: WhiteCardId, user: UserId) -> &WhiteCard {
+    // mechanism for getting nonexistent cards and updating the hand simultaneously. None if there's nothing left to replace it with
+    pub fn resolve_white_card(&mut self, id: WhiteCardId, user: UserId) -> Option<&WhiteCard> {
         if self.get_white_card(id).is_none() {
-            let card = self.draw_white();
+            let card = self.draw_white()?;
             //goddamnit intellij
             #[allow(unused_mut)]
             let mut hand = self.get_hand(user);
@@ -156,23 +402,21 @@ impl CahManager {
             if let Some(idx) = idx {
                 hand[idx] = card;
             }
-            self.get_white_card(card).unwrap()
+            self.get_white_card(card)
         } else {
-            self.get_white_card(id).unwrap()
+            self.get_white_card(id)
         }
     }
-    pub fn draw_white(&mut self) -> WhiteCardId {
-        self.white_deck_state.pop_front().unwrap_or(WhiteCardId::default())
+    pub fn draw_white(&mut self) -> Option<WhiteCardId> {
+        self.white_deck_state.pop_front()
     }
-    pub fn draw_black(&mut self) -> &BlackCard {
-        if self.black_deck_state.len() == 0 {
-            let mut black_deck_state = self.black_deck.keys().cloned().collect::<Vec<_>>();
-            rand::thread_rng().shuffle(&mut black_deck_state);
-            self.black_deck_state = black_deck_state.into();
+    // None if there aren't any black cards to play at all
+    pub fn draw_black(&mut self) -> Option<&BlackCard> {
+        if self.black_deck_state.is_empty() {
+            self.black_deck_state = self.black_pile();
         }
-        let id = self.black_deck_state.pop_front().unwrap();
-        self.get_black_card(id).unwrap()
-
+        let id = self.black_deck_state.pop_front()?;
+        self.get_black_card(id)
     }
     pub fn get_players(&self) -> &PlayerContainer {
         &self.players
@@ -184,49 +428,209 @@ impl CahManager {
         self.state
     }
     pub fn set_state(&mut self, state: State) {
+        // reactions only count while the answers are being read
+        if state != State::Reading {
+            self.submissions.clear();
+            self.submissions_message = None;
+            self.reaction_picks.clear();
+        }
         self.state = state;
     }
     pub fn draw_into_hand(&mut self, user: UserId, amount: usize) {
         for _ in 0..amount {
-            let draw = self.draw_white();
-            self.get_hand(user).push(draw);
+            match self.draw_white() {
+                Some(draw) => self.get_hand(user).push(draw),
+                None => break,
+            }
         }
     }
     pub fn get_current_black_card(&self) -> Option<BlackCardId> {
         self.current_black_card
     }
+    // puts a specific card into play without going through (or disturbing) the draw pile
+    pub fn inject_black_card(&mut self, card: BlackCard) -> BlackCardId {
+        let id = card.id;
+        self.injected_black_card = Some(card);
+        self.current_black_card = Some(id);
+        id
+    }
+    pub fn is_final_round(&self) -> bool {
+        self.final_round
+    }
+    pub fn set_final_round(&mut self, final_round: bool) {
+        self.final_round = final_round;
+    }
+    // the player's main answer
     pub fn get_cards_in_play(&self, id: UserId) -> Option<&Vec<WhiteCardId>> {
-        self.in_play.get(&id)
+        self.in_play.get(&id).and_then(|x| x.first())
     }
     pub fn get_cards_in_play_mut(&mut self, id: UserId) -> &mut Vec<WhiteCardId> {
-        if self.in_play.contains_key(&id) {
-            self.in_play.get_mut(&id).unwrap()
-        } else {
-            let vec = Vec::with_capacity(5);
-            self.in_play.insert(id, vec);
-            self.in_play.get_mut(&id).unwrap()
+        let answers = self.in_play.entry(id).or_insert_with(Vec::new);
+        if answers.is_empty() {
+            answers.push(Vec::with_capacity(5));
         }
+        &mut answers[0]
+    }
+    // an extra answer on top of the main one, see HouseRule::Gambling
+    pub fn add_cards_in_play(&mut self, id: UserId, cards: Vec<WhiteCardId>) {
+        self.in_play.entry(id).or_insert_with(Vec::new).push(cards);
     }
     pub fn get_all_cards_in_play(&self) -> impl Iterator<Item=(UserId, &Vec<WhiteCardId>)> {
-        self.in_play.iter().map(|(x, y)| (*x, y))
+        self.in_play.iter().flat_map(|(x, y)| y.iter().map(move |z| (*x, z)))
     }
-    pub fn get_wins_mut(&mut self, id: UserId) -> &mut Vec<BlackCardId> {
-        if self.wins.contains_key(&id) {
-            self.wins.get_mut(&id).unwrap()
-        } else {
-            let vec = Vec::with_capacity(8);
-            self.wins.insert(id, vec);
-            self.wins.get_mut(&id).unwrap()
-        }
+    // the order here is what the czar sees and picks from, so it mustn't give away who played what.
+    // card ids would (write-ins are numbered as they come in), so it's random
+    pub fn shuffle_submissions(&mut self) {
+        let mut values = self.get_all_cards_in_play()
+            .filter(|(_, x)| !x.is_empty())
+            .map(|(id, x)| (id, x.iter().cloned().collect::<SmallVec<[WhiteCardId; 5]>>()))
+            .collect::<Vec<_>>();
+        rand::thread_rng().shuffle(&mut values);
+        self.submissions = values;
+    }
+    // in the order from shuffle_submissions
+    pub fn get_submissions(&self) -> Vec<(UserId, SmallVec<[WhiteCardId; 5]>)> {
+        self.submissions.clone()
+    }
+    pub fn get_wins(&self, id: UserId) -> &[(BlackCardId, u32)] {
+        self.wins.get(&id).map(|x| &x[..]).unwrap_or(&[])
+    }
+    // the points are what the card is worth if it's wagered later, and aren't added to the score here
+    pub fn add_win(&mut self, id: UserId, card: BlackCardId, points: u32) {
+        self.wins.entry(id).or_insert_with(|| Vec::with_capacity(8)).push((card, points));
     }
     pub fn clear_cards_in_play(&mut self) {
         for vec in self.in_play.values_mut() {
             vec.clear()
         }
     }
-    pub fn get_win_condition(&self) -> u8 {
+    // takes the player's latest black card (and the points that came with it) and holds it until the round is over
+    pub fn place_wager(&mut self, id: UserId) -> Option<(BlackCardId, u32)> {
+        let (card, points) = self.wins.get_mut(&id).and_then(|x| x.pop())?;
+        if let Some(score) = self.scores.get_mut(&id) {
+            *score = score.saturating_sub(points);
+        }
+        self.wagers.insert(id, (card, points));
+        Some((card, points))
+    }
+    pub fn get_wager(&self, id: UserId) -> Option<(BlackCardId, u32)> {
+        self.wagers.get(&id).cloned()
+    }
+    pub fn take_wagers(&mut self) -> Vec<(UserId, (BlackCardId, u32))> {
+        self.wagers.drain().collect()
+    }
+    pub fn get_score(&self, id: UserId) -> u32 {
+        self.scores.get(&id).cloned().unwrap_or(0)
+    }
+    pub fn get_scores(&self) -> impl Iterator<Item=(UserId, u32)> + '_ {
+        self.scores.iter().map(|(x, y)| (*x, *y))
+    }
+    // returns the new total
+    pub fn add_points(&mut self, id: UserId, points: u32) -> u32 {
+        let score = self.scores.entry(id).or_insert(0);
+        *score += points;
+        *score
+    }
+    pub fn get_win_condition(&self) -> u32 {
         self.win_condition
     }
+    pub fn set_win_condition(&mut self, win_condition: u32) {
+        self.win_condition = win_condition;
+    }
+    pub fn get_mode(&self) -> GameMode {
+        self.mode
+    }
+    pub fn set_mode(&mut self, mode: GameMode) {
+        self.mode = mode;
+    }
+    pub fn get_sfw_policy(&self) -> SfwPolicy {
+        self.sfw_policy
+    }
+    pub fn set_sfw_policy(&mut self, sfw_policy: SfwPolicy) {
+        self.sfw_policy = sfw_policy;
+    }
+    pub fn is_plain_text(&self) -> bool {
+        self.plain_text
+    }
+    pub fn set_plain_text(&mut self, plain_text: bool) {
+        self.plain_text = plain_text;
+    }
+    pub fn get_round(&self) -> u32 {
+        self.round
+    }
+    // returns the new round number
+    pub fn next_round(&mut self) -> u32 {
+        self.round += 1;
+        self.round_deadline = self.round_time_limit.map(|x| SystemTime::now() + x);
+        self.round
+    }
+    pub fn get_round_time_limit(&self) -> Option<Duration> {
+        self.round_time_limit
+    }
+    // takes effect from the next round
+    pub fn set_round_time_limit(&mut self, limit: Option<Duration>) {
+        self.round_time_limit = limit;
+    }
+    // when the current round's time runs out, if it has a limit
+    pub fn get_round_deadline(&self) -> Option<SystemTime> {
+        self.round_deadline
+    }
+    pub fn get_status_message(&self) -> Option<MessageId> {
+        self.status_message
+    }
+    pub fn set_status_message(&mut self, message: Option<MessageId>) {
+        self.status_message = message;
+    }
+    pub fn get_renderer(&self) -> Option<&CardRenderer> {
+        self.renderer.as_ref()
+    }
+    pub fn set_renderer(&mut self, renderer: Option<CardRenderer>) {
+        self.renderer = renderer;
+    }
+    pub fn get_judging(&self) -> Judging {
+        self.judging
+    }
+    pub fn set_judging(&mut self, judging: Judging) {
+        self.judging = judging;
+    }
+    // when the players vote, the czar only draws the black card and gets to answer like everyone else
+    pub fn is_answering(&self, id: UserId) -> bool {
+        self.judging == Judging::Vote || id != self.players.current()
+    }
+    pub fn get_answering_players(&self) -> Vec<UserId> {
+        self.players.all().iter().cloned().filter(|x| self.is_answering(*x)).collect()
+    }
+    pub fn cast_vote(&mut self, voter: UserId, submission: usize) {
+        self.votes.insert(voter, submission);
+    }
+    pub fn remove_vote(&mut self, voter: UserId) {
+        self.votes.remove(&voter);
+    }
+    pub fn get_votes(&self) -> &HashMap<UserId, usize> {
+        &self.votes
+    }
+    pub fn clear_votes(&mut self) {
+        self.votes.clear();
+    }
+    pub fn get_submissions_message(&self) -> Option<MessageId> {
+        self.submissions_message
+    }
+    pub fn set_submissions_message(&mut self, message: Option<MessageId>) {
+        self.submissions_message = message;
+    }
+    pub fn get_reaction_picks_mut(&mut self) -> &mut SmallVec<[usize; 3]> {
+        &mut self.reaction_picks
+    }
+    pub fn has_rule(&self, rule: HouseRule) -> bool {
+        self.house_rules.contains(&rule)
+    }
+    pub fn set_rule(&mut self, rule: HouseRule, enabled: bool) {
+        if enabled {
+            self.house_rules.insert(rule);
+        } else {
+            self.house_rules.remove(&rule);
+        }
+    }
 }
 
 impl Key for CahManager {
@@ -237,50 +641,186 @@ impl Key for CahManager {
 pub struct WhiteCard {
     pub message: String,
     pub id: WhiteCardId,
+    #[serde(default)]
+    pub blank: bool,
+    #[serde(default)]
+    pub tags: Vec<String>,
 }
 
+pub const BLANK_CARD_TEXT: &str = "(Blank card - write your own answer)";
+// blanks don't come from the deck file, so they get ids the file wouldn't use
+const BLANK_ID_START: u64 = 1 << 62;
+// the space keeps it from being a valid deck name (see deck::is_valid_deck_name), so no real deck can share its key
+const WRITE_IN_DECK: &str = "write in";
+
 #[derive(Serialize, Deserialize, Clone, PartialEq, Eq, PartialOrd, Ord, Debug, Hash)]
 pub struct BlackCard {
     pub message: String,
     pub draw: u8,
     pub play: u8,
     pub id: BlackCardId,
+    #[serde(default)]
+    pub tags: Vec<String>,
+}
+
+#[derive(Copy, Clone, Eq, Hash, PartialEq, Debug)]
+pub enum State {
+    Off,
+    Playing,
+    Reading,
+    Waiting,
+}
+
+#[derive(Copy, Clone, Eq, Hash, PartialEq, Debug)]
+pub enum GameMode {
+    Cards,
+    // no hands; everyone writes their own answers
+    FreeText,
 }
 
-pub fn parse_white_cards<R>(read: R) -> Result<Vec<WhiteCard>, CsvError> where R: Read {
-    let mut reader = Reader::from_reader(read);
-    let mut vec = Vec::new();
-    for record in reader.deserialize() {
-        vec.push(record?)
+impl FromStr for GameMode {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "cards" => Ok(GameMode::Cards),
+            "free-text" => Ok(GameMode::FreeText),
+            _ => Err(format!("Unknown game mode '{}' (try 'cards' or 'free-text')", s)),
+        }
     }
-    Ok(vec)
 }
 
-pub fn parse_black_cards<R>(read: R) -> Result<Vec<BlackCard>, CsvError> where R: Read {
-    let mut reader = Reader::from_reader(read);
-    let mut vec = Vec::new();
-    for record in reader.deserialize() {
-        vec.push(record?)
+impl Display for GameMode {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        match self {
+            GameMode::Cards => f.write_str("cards"),
+            GameMode::FreeText => f.write_str("free-text"),
+        }
     }
-    Ok(vec)
 }
 
 #[derive(Copy, Clone, Eq, Hash, PartialEq, Debug)]
-pub enum State {
-    Off,
-    Playing,
-    Reading,
-    Waiting,
+pub enum Judging {
+    Czar,
+    Vote,
 }
 
-pub fn load_deck(name: &str) -> Result<(Vec<BlackCard>, Vec<WhiteCard>), IoError> {
-    //todo cardcast
-    let root = Path::new("decks");
-    let white_file = File::open(root.join(&name).join("white.csv"))?;
-    let white = parse_white_cards(white_file)?;
-    let black_file = File::open(root.join(&name).join("black.csv"))?;
-    let black = parse_black_cards(black_file)?;
-    Ok((black, white))
+impl FromStr for Judging {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "czar" => Ok(Judging::Czar),
+            "vote" => Ok(Judging::Vote),
+            _ => Err(format!("Unknown judging '{}' (try 'czar' or 'vote')", s)),
+        }
+    }
+}
+
+impl Display for Judging {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        match self {
+            Judging::Czar => f.write_str("czar"),
+            Judging::Vote => f.write_str("vote"),
+        }
+    }
+}
+
+// picks cards by their tags. once anything's included, a card needs at least one of those tags
+#[derive(Clone, Default, Debug)]
+pub struct TagFilter {
+    pub include: Vec<String>,
+    pub exclude: Vec<String>,
+}
+
+impl TagFilter {
+    pub fn is_empty(&self) -> bool {
+        self.include.is_empty() && self.exclude.is_empty()
+    }
+    // what a tag in set-decks can be, the same characters a deck name can have
+    pub fn is_valid_tag(tag: &str) -> bool {
+        !tag.is_empty() && tag.chars().all(|c: char| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+    }
+    pub fn allows(&self, tags: &[String]) -> bool {
+        (self.include.is_empty() || tags.iter().any(|x| self.include.contains(x))) && !tags.iter().any(|x| self.exclude.contains(x))
+    }
+}
+
+impl Display for TagFilter {
+    // the same way they're given to cah set-decks, escaped since it's sent back to the channel
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        let include = self.include.iter().map(|x| format!("+tag:{}", display::escape(x)));
+        let exclude = self.exclude.iter().map(|x| format!("-tag:{}", display::escape(x)));
+        f.write_str(&include.chain(exclude).collect::<Vec<_>>().join(" "))
+    }
+}
+
+// what cah new does in a channel that isn't marked NSFW, when some of the decks aren't family decks
+#[derive(Deserialize, Copy, Clone, Eq, Hash, PartialEq, Debug)]
+#[serde(rename_all = "kebab-case")]
+pub enum SfwPolicy {
+    Refuse,
+    FamilyOnly,
+}
+
+impl Default for SfwPolicy {
+    fn default() -> Self {
+        SfwPolicy::Refuse
+    }
+}
+
+// whether a card makes it into the piles. blanks don't have tags, but they're not about anything either
+pub fn white_allowed(card: &WhiteCard, tags: &TagFilter, bans: &BanList) -> bool {
+    !bans.bans_white(card) && (card.blank || tags.allows(&card.tags))
+}
+
+pub fn black_allowed(card: &BlackCard, tags: &TagFilter, bans: &BanList) -> bool {
+    !bans.bans_black(card) && tags.allows(&card.tags)
+}
+
+// same id as in the base deck, so it's the same card whether or not base is loaded
+pub fn haiku_card() -> BlackCard {
+    BlackCard {
+        message: "Make a haiku.".to_string(),
+        draw: 2,
+        play: 3,
+        id: BlackCardId::new("base", 14),
+        tags: Vec::new(),
+    }
+}
+
+#[derive(Copy, Clone, Eq, Hash, PartialEq, Debug)]
+pub enum HouseRule {
+    // the czar ranks the top three answers for 3/2/1 points
+    SeriousBusiness,
+    // once someone wins, everyone plays one last haiku for glory
+    HappyEnding,
+    // bet a black card you've won to play a second answer
+    Gambling,
+}
+
+impl HouseRule {
+    pub const ALL: &'static [HouseRule] = &[HouseRule::SeriousBusiness, HouseRule::HappyEnding, HouseRule::Gambling];
+}
+
+impl FromStr for HouseRule {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "serious-business" => Ok(HouseRule::SeriousBusiness),
+            "happy-ending" => Ok(HouseRule::HappyEnding),
+            "gambling" => Ok(HouseRule::Gambling),
+            _ => Err(format!("Unknown house rule '{}'", s)),
+        }
+    }
+}
+
+impl Display for HouseRule {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        match self {
+            HouseRule::SeriousBusiness => f.write_str("serious-business"),
+            HouseRule::HappyEnding => f.write_str("happy-ending"),
+            HouseRule::Gambling => f.write_str("gambling"),
+        }
+    }
 }
 
 pub struct PlayerContainer {
@@ -377,18 +917,292 @@ impl<R> RngExt for R where R: Rng {
 }
 
 pub fn format_card(card: &BlackCard, fills: &[&WhiteCard]) -> String {
-    let mut pattern = card.message.clone();
+    card_segments(card, fills).into_iter()
+        .map(|(text, answer)| if answer { format!("**{}**", display::escape(&text)) } else { display::escape(&text) })
+        .collect()
+}
+
+// the card's own text and the answers in it, in order, as (text, whether it's an answer)
+pub fn card_segments(card: &BlackCard, fills: &[&WhiteCard]) -> Vec<(String, bool)> {
+    let mut segments = Vec::new();
+    let mut before = String::with_capacity(card.message.len() + fills.iter().map(|x| x.message.len()).sum::<usize>());
+    let mut rest = &card.message[..];
+    let mut fills = fills.iter();
+    while let Some((start, end)) = find_blank(rest) {
+        let fill = match fills.next() {
+            Some(fill) => fill,
+            None => break,
+        };
+        before.push_str(&rest[..start]);
+        segments.push((rest[..start].to_string(), false));
+        let answer = fit_answer(&fill.message, starts_sentence(&before));
+        before.push_str(&answer);
+        segments.push((answer, true));
+        rest = &rest[end..];
+    }
+    segments.push((rest.to_string(), false));
+    // question cards (or more answers than blanks): the answer goes after the card, as written
     for fill in fills {
-        if let Some(offset) = pattern.find("___") {
-            pattern.replace_range(offset..offset + 3, &fill.message)
-        } else {
-            break;
+        segments.push((" ".to_string(), false));
+        segments.push((fill.message.trim().to_string(), true));
+    }
+    segments
+}
+
+pub fn count_blanks(message: &str) -> usize {
+    let mut count = 0;
+    let mut rest = message;
+    while let Some((_, end)) = find_blank(rest) {
+        count += 1;
+        rest = &rest[end..];
+    }
+    count
+}
+
+// decks don't agree on how long a blank is, so any run of two or more underscores counts.
+// a single one is left alone, it's more likely to be someone's _emphasis_
+fn find_blank(message: &str) -> Option<(usize, usize)> {
+    let mut offset = 0;
+    loop {
+        let start = offset + message[offset..].find('_')?;
+        let end = message[start..].find(|c: char| c != '_').map(|x| start + x).unwrap_or(message.len());
+        if end - start >= 2 {
+            return Some((start, end));
         }
+        offset = end;
+    }
+}
+
+fn starts_sentence(before: &str) -> bool {
+    let before = before.trim_end_matches(|c: char| c.is_whitespace() || c == '"' || c == '*' || c == '(');
+    before.is_empty() || before.ends_with(|c: char| c == '.' || c == '!' || c == '?' || c == ':')
+}
+
+// white cards are written as standalone sentences, which reads badly in the middle of someone else's
+fn fit_answer(answer: &str, sentence_start: bool) -> String {
+    // ! and ? are part of the joke, full stops aren't
+    let answer = answer.trim().trim_end_matches(|c: char| c == '.' || c == ',' || c == ';');
+    let mut chars = answer.chars();
+    let first = match chars.next() {
+        Some(first) => first,
+        None => return String::new(),
+    };
+    let first_word = answer.split_whitespace().next().unwrap_or("");
+    if sentence_start {
+        first.to_uppercase().chain(chars).collect()
+    } else if LOWERCASE_WORDS.contains(&first_word) {
+        // there's no telling a name from the start of a sentence, so only lowercase words that can't be names
+        first.to_lowercase().chain(chars).collect()
+    } else {
+        answer.to_string()
+    }
+}
+
+const LOWERCASE_WORDS: &[&str] = &[
+    "A", "An", "The", "My", "Your", "His", "Her", "Its", "Our", "Their", "This", "That", "These", "Those",
+    "Some", "Any", "All", "Every", "No", "Not", "One", "Two", "Three", "Several", "Many", "Only", "Just",
+];
+
+// FNV-1a, which unlike the std hasher is guaranteed to give the same result every run
+pub fn stable_hash(s: &str) -> u64 {
+    let mut hash = 0xcbf2_9ce4_8422_2325u64;
+    for byte in s.bytes() {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
     }
-    pattern
+    hash
 }
 
+// identifies a deck by a hash of its name, so ids stay the same across restarts without keeping a registry
 #[derive(Debug, Hash, Eq, PartialEq, Ord, PartialOrd, Serialize, Deserialize, Copy, Clone, Default)]
-pub struct BlackCardId(u64);
+pub struct DeckKey(u64);
+
+impl DeckKey {
+    pub fn of(deck: &str) -> Self {
+        DeckKey(stable_hash(deck))
+    }
+}
+
+// card ids are only unique within their deck, so the deck is part of the id
 #[derive(Debug, Hash, Eq, PartialEq, Ord, PartialOrd, Serialize, Deserialize, Copy, Clone, Default)]
-pub struct WhiteCardId(u64);
+pub struct BlackCardId(DeckKey, u64);
+#[derive(Debug, Hash, Eq, PartialEq, Ord, PartialOrd, Serialize, Deserialize, Copy, Clone, Default)]
+pub struct WhiteCardId(DeckKey, u64);
+
+impl BlackCardId {
+    pub fn new(deck: &str, number: u64) -> Self {
+        BlackCardId(DeckKey::of(deck), number)
+    }
+    pub fn deck(&self) -> DeckKey {
+        self.0
+    }
+    pub fn number(&self) -> u64 {
+        self.1
+    }
+}
+
+impl WhiteCardId {
+    pub fn new(deck: &str, number: u64) -> Self {
+        WhiteCardId(DeckKey::of(deck), number)
+    }
+    pub fn deck(&self) -> DeckKey {
+        self.0
+    }
+    pub fn number(&self) -> u64 {
+        self.1
+    }
+}
+
+// `deck:number`, the same way the cards are shown to players
+fn parse_card_id(s: &str) -> Result<(&str, u64), String> {
+    let idx = s.rfind(':').ok_or_else(|| format!("'{}' is not a card id (expected deck:number)", s))?;
+    let number = s[idx + 1..].parse::<u64>().map_err(|_| format!("'{}' is not a card id (expected deck:number)", s))?;
+    Ok((&s[..idx], number))
+}
+
+impl FromStr for BlackCardId {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        parse_card_id(s).map(|(deck, number)| BlackCardId::new(deck, number))
+    }
+}
+
+impl FromStr for WhiteCardId {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        parse_card_id(s).map(|(deck, number)| WhiteCardId::new(deck, number))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn black(message: &str) -> BlackCard {
+        BlackCard { message: message.to_string(), draw: 0, play: 1, id: BlackCardId::default(), tags: Vec::new() }
+    }
+
+    fn white(message: &str) -> WhiteCard {
+        WhiteCard { message: message.to_string(), id: WhiteCardId::default(), blank: false, tags: Vec::new() }
+    }
+
+    fn format(card: &str, fills: &[&str]) -> String {
+        let fills = fills.iter().map(|x| white(x)).collect::<Vec<_>>();
+        format_card(&black(card), &fills.iter().collect::<Vec<_>>())
+    }
+
+    #[test]
+    fn mid_sentence_answers_lose_their_capital_and_full_stop() {
+        assert_eq!(format("I drink to forget ____.", &["A bad idea."]), "I drink to forget **a bad idea**.");
+    }
+
+    #[test]
+    fn names_keep_their_capitals() {
+        assert_eq!(format("Dinner with ____.", &["Sting."]), "Dinner with **Sting**.");
+        assert_eq!(format("The real reason for ____.", &["Beijing."]), "The real reason for **Beijing**.");
+    }
+
+    #[test]
+    fn sentence_starts_are_capitalised() {
+        assert_eq!(format("____. That's how I want to die.", &["the Big Bang."]), "**The Big Bang**. That's how I want to die.");
+        assert_eq!(format("Step 1: ____. Step 2: ____. Step 3: Profit.", &["Lying.", "steal underpants."]), "Step 1: **Lying**. Step 2: **Steal underpants**. Step 3: Profit.");
+    }
+
+    #[test]
+    fn question_cards_put_the_answer_after() {
+        assert_eq!(format("What's that smell?", &["Bees?"]), "What's that smell? **Bees?**");
+    }
+
+    #[test]
+    fn extra_fills_go_on_the_end() {
+        assert_eq!(format("____ is cool.", &["Cats.", "Dogs."]), "**Cats** is cool. **Dogs.**");
+    }
+
+    #[test]
+    fn blanks_need_two_underscores() {
+        assert_eq!(count_blanks("Some _emphasis_ and a ____."), 1);
+        assert_eq!(count_blanks("__ and ________"), 2);
+        assert_eq!(format("I _really_ want ____.", &["A dog."]), "I \\_really\\_ want **a dog**.");
+    }
+
+    // a pile of cards from each deck, given as (deck, how many)
+    fn weighted_pile(weights: &[(&str, u32)], pile: &[(&str, usize)]) -> Vec<(DeckKey, usize)> {
+        let mut manager = CahManager::new(Vec::new(), Vec::new(), Vec::new());
+        manager.set_deck_weights(weights.iter().map(|&(name, weight)| (DeckKey::of(name), weight)).collect());
+        let mut cards = pile.iter().flat_map(|&(name, count)| vec![DeckKey::of(name); count]).collect::<VecDeque<_>>();
+        manager.apply_weights(&mut cards, |x: &DeckKey| *x);
+        pile.iter().map(|&(name, _)| (DeckKey::of(name), cards.iter().filter(|x| **x == DeckKey::of(name)).count())).collect()
+    }
+
+    #[test]
+    fn no_weights_leaves_the_pile_alone() {
+        assert_eq!(weighted_pile(&[], &[("a", 10), ("b", 3)]), vec![(DeckKey::of("a"), 10), (DeckKey::of("b"), 3)]);
+    }
+
+    #[test]
+    fn weights_already_in_proportion_keep_everything() {
+        assert_eq!(weighted_pile(&[("a", 1), ("b", 3)], &[("a", 10), ("b", 30)]), vec![(DeckKey::of("a"), 10), (DeckKey::of("b"), 30)]);
+    }
+
+    #[test]
+    fn the_smallest_share_sets_the_pile_size() {
+        // b can only fill 3/4 of a pile of 13, so a gets cut to the other quarter
+        assert_eq!(weighted_pile(&[("a", 1), ("b", 3)], &[("a", 10), ("b", 10)]), vec![(DeckKey::of("a"), 3), (DeckKey::of("b"), 10)]);
+        assert_eq!(weighted_pile(&[("a", 1), ("b", 1)], &[("a", 50), ("b", 5)]), vec![(DeckKey::of("a"), 5), (DeckKey::of("b"), 5)]);
+    }
+
+    #[test]
+    fn weights_only_count_decks_in_the_pile() {
+        // c has nothing left, so it doesn't shrink the others to nothing
+        assert_eq!(weighted_pile(&[("a", 1), ("b", 1), ("c", 1)], &[("a", 4), ("b", 4)]), vec![(DeckKey::of("a"), 4), (DeckKey::of("b"), 4)]);
+    }
+
+    fn tags(include: &[&str], exclude: &[&str]) -> TagFilter {
+        TagFilter { include: include.iter().map(|x| x.to_string()).collect(), exclude: exclude.iter().map(|x| x.to_string()).collect() }
+    }
+
+    fn tagged(card_tags: &[&str]) -> Vec<String> {
+        card_tags.iter().map(|x| x.to_string()).collect()
+    }
+
+    #[test]
+    fn an_empty_filter_allows_everything() {
+        assert!(tags(&[], &[]).allows(&tagged(&[])));
+        assert!(tags(&[], &[]).allows(&tagged(&["politics"])));
+    }
+
+    #[test]
+    fn included_tags_need_any_one_of_them() {
+        let filter = tags(&["food", "animals"], &[]);
+        assert!(filter.allows(&tagged(&["animals", "pop-culture"])));
+        assert!(!filter.allows(&tagged(&["politics"])));
+        assert!(!filter.allows(&tagged(&[])));
+    }
+
+    #[test]
+    fn excluded_tags_win_over_included_ones() {
+        let filter = tags(&["food"], &["politics"]);
+        assert!(!filter.allows(&tagged(&["food", "politics"])));
+        assert!(tags(&[], &["politics"]).allows(&tagged(&[])));
+    }
+
+    #[test]
+    fn blanks_ignore_the_tags_but_not_the_bans() {
+        let mut blank = white("");
+        blank.blank = true;
+        blank.id = WhiteCardId::new("base", 7);
+        assert!(white_allowed(&blank, &tags(&["food"], &[]), &BanList::default()));
+        let bans = BanList { white: vec!["base:7".to_string()], ..BanList::default() };
+        assert!(!white_allowed(&blank, &tags(&[], &[]), &bans));
+    }
+
+    #[test]
+    fn cards_need_to_pass_the_tags_and_the_bans() {
+        let mut card = black("Why am I sticky? ____.");
+        card.tags = tagged(&["gross"]);
+        assert!(black_allowed(&card, &tags(&["gross"], &[]), &BanList::default()));
+        assert!(!black_allowed(&card, &tags(&[], &["gross"]), &BanList::default()));
+        let bans = BanList { words: vec!["sticky".to_string()], ..BanList::default() };
+        assert!(!black_allowed(&card, &tags(&["gross"], &[]), &bans));
+    }
+}
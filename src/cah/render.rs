@@ -0,0 +1,107 @@
+use image::{DynamicImage, ImageOutputFormat, Rgb, RgbImage};
+use rusttype::{point, Font, Scale};
+use std::fs::File;
+use std::io::{Error as IoError, ErrorKind, Read};
+use super::{BlackCard, WhiteCard, card_segments};
+
+// a 2.5x3.5 card at 200dpi
+const WIDTH: u32 = 500;
+const HEIGHT: u32 = 700;
+const MARGIN: f32 = 40.0;
+const FONT_SIZE: f32 = 38.0;
+// long cards shrink a step at a time until they fit
+const FONT_STEP: f32 = 2.0;
+const MIN_FONT_SIZE: f32 = 20.0;
+const BACKGROUND: [u8; 3] = [0, 0, 0];
+const TEXT: [u8; 3] = [255, 255, 255];
+// for the answers filled in by the white cards
+const HIGHLIGHT: [u8; 3] = [255, 205, 40];
+
+pub struct CardRenderer {
+    font: Font<'static>,
+}
+
+impl CardRenderer {
+    pub fn load(path: &str) -> Result<CardRenderer, IoError> {
+        let mut bytes = Vec::new();
+        File::open(path)?.read_to_end(&mut bytes)?;
+        let font = Font::from_bytes(bytes).map_err(|x| IoError::new(ErrorKind::InvalidData, format!("{}: {}", path, x)))?;
+        Ok(CardRenderer { font })
+    }
+    // the same text format_card makes, as a PNG. None if it won't fit even at the smallest font size, so it can go as text instead
+    pub fn render_black_card(&self, card: &BlackCard, fills: &[&WhiteCard]) -> Result<Option<Vec<u8>>, IoError> {
+        let mut size = FONT_SIZE;
+        let (words, scale) = loop {
+            let scale = Scale::uniform(size);
+            if let Some(words) = self.layout(card, fills, scale) {
+                break (words, scale);
+            }
+            size -= FONT_STEP;
+            if size < MIN_FONT_SIZE {
+                return Ok(None);
+            }
+        };
+        let mut image = RgbImage::from_pixel(WIDTH, HEIGHT, Rgb(BACKGROUND));
+        for (word, x, y, colour) in words {
+            self.draw(&mut image, &word, scale, x, y, colour);
+        }
+        let mut png = Vec::new();
+        DynamicImage::ImageRgb8(image).write_to(&mut png, ImageOutputFormat::PNG)
+            .map_err(|x| IoError::new(ErrorKind::Other, x.to_string()))?;
+        Ok(Some(png))
+    }
+    // where each word goes, or None if they run off the card
+    fn layout(&self, card: &BlackCard, fills: &[&WhiteCard], scale: Scale) -> Option<Vec<(String, f32, f32, [u8; 3])>> {
+        let v_metrics = self.font.v_metrics(scale);
+        let line_height = v_metrics.ascent - v_metrics.descent + v_metrics.line_gap;
+        let space = self.width(" ", scale);
+        let mut words = Vec::new();
+        let mut x = MARGIN;
+        let mut y = MARGIN + v_metrics.ascent;
+        for (segment, answer) in card_segments(card, fills) {
+            let colour = if answer { HIGHLIGHT } else { TEXT };
+            // segments don't always start on a word, e.g. the full stop after an answer, so the spaces are kept track of here
+            for (word_idx, word) in segment.split(' ').enumerate() {
+                if word_idx > 0 {
+                    x += space;
+                }
+                if word.is_empty() {
+                    continue;
+                }
+                let width = self.width(word, scale);
+                if x + width > WIDTH as f32 - MARGIN && x > MARGIN + space {
+                    x = MARGIN;
+                    y += line_height;
+                }
+                if x + width > WIDTH as f32 - MARGIN || y - v_metrics.descent > HEIGHT as f32 - MARGIN {
+                    return None;
+                }
+                words.push((word.to_string(), x, y, colour));
+                x += width;
+            }
+        }
+        Some(words)
+    }
+    fn width(&self, text: &str, scale: Scale) -> f32 {
+        self.font.layout(text, scale, point(0.0, 0.0)).map(|x| x.unpositioned().h_metrics().advance_width).sum()
+    }
+    fn draw(&self, image: &mut RgbImage, text: &str, scale: Scale, x: f32, y: f32, colour: [u8; 3]) {
+        for glyph in self.font.layout(text, scale, point(x, y)) {
+            let bounds = match glyph.pixel_bounding_box() {
+                Some(bounds) => bounds,
+                None => continue,
+            };
+            glyph.draw(|glyph_x, glyph_y, coverage| {
+                let (pixel_x, pixel_y) = (bounds.min.x + glyph_x as i32, bounds.min.y + glyph_y as i32);
+                // layout keeps the words on the card, this is just for the odd glyph that reaches past its advance
+                if pixel_x < 0 || pixel_y < 0 || pixel_x as u32 >= WIDTH || pixel_y as u32 >= HEIGHT {
+                    return;
+                }
+                let pixel = image.get_pixel_mut(pixel_x as u32, pixel_y as u32);
+                for (channel, target) in pixel.data.iter_mut().zip(colour.iter()) {
+                    *channel = (*channel as f32 * (1.0 - coverage) + *target as f32 * coverage) as u8;
+                }
+            });
+        }
+    }
+}
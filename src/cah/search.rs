@@ -0,0 +1,115 @@
+use serenity::model::id::GuildId;
+use std::collections::{HashMap, HashSet};
+use std::fmt::{Display, Formatter, Result as FmtResult};
+use std::io::Error as IoError;
+use super::{BlackCard, WhiteCard, deck, display, parse_card_id};
+
+pub const PAGE_SIZE: usize = 10;
+
+pub enum FoundCard {
+    Black(BlackCard),
+    White(WhiteCard),
+}
+
+impl FoundCard {
+    pub fn message(&self) -> &str {
+        match self {
+            FoundCard::Black(card) => &card.message,
+            FoundCard::White(card) => &card.message,
+        }
+    }
+    pub fn number(&self) -> u64 {
+        match self {
+            FoundCard::Black(card) => card.id.number(),
+            FoundCard::White(card) => card.id.number(),
+        }
+    }
+}
+
+// a card along with the deck it came from, since the ids only hold a hash of the deck's name
+pub struct IndexedCard {
+    pub deck: String,
+    pub deck_name: String,
+    pub card: FoundCard,
+}
+
+impl Display for IndexedCard {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        write!(f, "`{}:{}` ({}, ", self.deck, self.card.number(), display::escape(&self.deck_name))?;
+        match self.card {
+            FoundCard::Black(ref card) => write!(f, "black, pick {} draw {}", card.play, card.draw)?,
+            FoundCard::White(_) => f.write_str("white")?,
+        }
+        write!(f, ") {}", display::escape(self.card.message()))
+    }
+}
+
+pub fn words(text: &str) -> impl Iterator<Item = String> + '_ {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|x| !x.is_empty())
+        .map(|x| x.to_lowercase())
+}
+
+fn deck_cards(name: &str, guild: Option<GuildId>) -> Result<Vec<IndexedCard>, IoError> {
+    let (black, white) = deck::load_deck(name, guild)?;
+    let deck_name = deck::load_manifest(name, guild).ok().and_then(|x| x.name).unwrap_or_else(|| name.to_string());
+    let black = black.into_iter().map(FoundCard::Black);
+    let white = white.into_iter().map(FoundCard::White);
+    Ok(black.chain(white).map(|card| IndexedCard { deck: name.to_string(), deck_name: deck_name.clone(), card }).collect())
+}
+
+// every card a guild can use, looked up by the words on it
+pub struct CardIndex {
+    cards: Vec<IndexedCard>,
+    words: HashMap<String, HashSet<usize>>,
+}
+
+impl CardIndex {
+    // decks that fail to load are left out, cah check-decks is the place to hear about them
+    pub fn build(guild: Option<GuildId>) -> Result<CardIndex, IoError> {
+        let mut cards = Vec::new();
+        for name in deck::available_decks(guild)? {
+            if let Ok(mut found) = deck_cards(&name, guild) {
+                cards.append(&mut found);
+            }
+        }
+        let mut words = HashMap::new();
+        for (idx, card) in cards.iter().enumerate() {
+            for word in self::words(card.card.message()) {
+                words.entry(word).or_insert_with(HashSet::new).insert(idx);
+            }
+        }
+        Ok(CardIndex { cards, words })
+    }
+    // cards with every word in the query on them, in deck order
+    pub fn search(&self, query: &str) -> Vec<&IndexedCard> {
+        let mut postings = Vec::new();
+        for word in words(query) {
+            match self.words.get(&word) {
+                Some(posting) => postings.push(posting),
+                None => return Vec::new(),
+            }
+        }
+        // the rarest word goes first so there's less to check against the rest
+        postings.sort_by_key(|x| x.len());
+        let (first, rest) = match postings.split_first() {
+            Some(split) => split,
+            None => return Vec::new(),
+        };
+        let mut found = first.iter().cloned().filter(|x| rest.iter().all(|y| y.contains(x))).collect::<Vec<_>>();
+        found.sort();
+        found.into_iter().map(|x| &self.cards[x]).collect()
+    }
+}
+
+// `deck:number`. black and white cards are numbered separately, so there can be one of each
+pub fn find_card(id: &str, guild: Option<GuildId>) -> Result<Vec<IndexedCard>, String> {
+    let (name, number) = parse_card_id(id)?;
+    let mut cards = deck_cards(name, guild).map_err(|x| x.to_string())?;
+    cards.retain(|x| x.card.number() == number);
+    if cards.is_empty() {
+        Err(format!("There's no card {}", id))
+    } else {
+        Ok(cards)
+    }
+}
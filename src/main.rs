@@ -8,25 +8,56 @@ extern crate csv;
 extern crate serde;
 #[macro_use]
 extern crate serde_derive;
+extern crate serde_json;
 extern crate toml;
 extern crate smallvec;
 extern crate rand;
+extern crate image;
+extern crate rusttype;
 
+use std::env;
 use std::mem;
-use std::fs::File;
+use std::process;
+use std::fs::{self, File};
 use std::io::Read;
 use std::path::Path;
 use self::cah::CahManager;
 use serenity::client::{Client, Context, EventHandler};
 use serenity::framework::standard::{Args, CommandError, StandardFramework};
-use serenity::model::channel::Message;
+use serenity::model::channel::{Message, Reaction};
 use serenity::model::id::UserId;
+use serenity::model::permissions::Permissions;
 
 pub mod cah;
 
 type CommandResult = Result<(), CommandError>;
 
 fn main() {
+    if env::args().nth(1).map(|x| x == "--check-decks").unwrap_or(false) {
+        // check-only mode, doesn't need a config or a connection
+        let mut decks = env::args().skip(2).collect::<Vec<_>>();
+        if decks.is_empty() {
+            decks = cah::deck::available_decks(None).expect("Error listing decks");
+        }
+        let problems = cah::deck::check_decks(&decks, None);
+        for problem in problems.iter() {
+            println!("{}", problem);
+        }
+        println!("Checked {} decks, found {} problems", decks.len(), problems.len());
+        process::exit(if problems.is_empty() { 0 } else { 1 });
+    }
+    if env::args().nth(1).map(|x| x == "--export-deck").unwrap_or(false) {
+        // --export-deck <deck> <csv|json|html> [folder]
+        let name = env::args().nth(2).expect("Usage: --export-deck <deck> <csv|json|html> [folder]");
+        let format = env::args().nth(3).unwrap_or_else(|| "csv".to_string()).parse::<cah::export::ExportFormat>().expect("Error reading format");
+        let folder = env::args().nth(4).unwrap_or_else(|| ".".to_string());
+        for (file_name, contents) in cah::export::export_deck(&name, None, format).expect("Error exporting deck") {
+            let path = Path::new(&folder).join(file_name);
+            fs::write(&path, contents).expect("Error writing export");
+            println!("Wrote {}", path.display());
+        }
+        return;
+    }
     let mut config_file = File::open("romeo.toml").expect("Error opening config");
     let mut config_str = String::new();
     config_file.read_to_string(&mut config_str).expect("Error loading config");
@@ -40,45 +71,110 @@ fn main() {
             .command("cah cards", |c| c.dm_only(false /*todo*/).exec(cah::commands::my_cards))
             .command("cah draw", |c| c.guild_only(true).exec(cah::commands::draw_black_card))
             .command("cah play", |c| c.dm_only(false /*todo*/).exec(cah::commands::play_white_card))
+            .command("cah gamble", |c| c.dm_only(false /*todo*/).exec(cah::commands::gamble))
             .on("cah decks", cah::commands::get_decks)
+            .command("cah check-decks", |c| c.required_permissions(Permissions::ADMINISTRATOR).exec(cah::commands::check_decks))
+            .command("cah deck upload", |c| c.guild_only(true).required_permissions(Permissions::ADMINISTRATOR).exec(cah::commands::upload_deck))
+            .command("cah deck create", |c| c.guild_only(true).required_permissions(Permissions::ADMINISTRATOR).exec(cah::commands::create_deck))
+            .command("cah deck add-white", |c| c.guild_only(true).required_permissions(Permissions::ADMINISTRATOR).exec(cah::commands::add_white_card))
+            .command("cah deck add-black", |c| c.guild_only(true).required_permissions(Permissions::ADMINISTRATOR).exec(cah::commands::add_black_card))
+            .command("cah deck remove", |c| c.guild_only(true).required_permissions(Permissions::ADMINISTRATOR).exec(cah::commands::remove_card))
+            .command("cah deck list", |c| c.guild_only(true).exec(cah::commands::list_deck))
+            .on("cah search", cah::commands::search_cards)
+            .on("cah card", cah::commands::show_card)
+            .command("cah ban", |c| c.guild_only(true).required_permissions(Permissions::ADMINISTRATOR).exec(cah::commands::ban))
+            .command("cah unban", |c| c.guild_only(true).required_permissions(Permissions::ADMINISTRATOR).exec(cah::commands::unban))
+            .command("cah bans", |c| c.guild_only(true).exec(cah::commands::list_bans))
+            .command("cah add-deck", |c| c.guild_only(true).exec(cah::commands::add_deck))
+            .command("cah remove-deck", |c| c.guild_only(true).exec(cah::commands::remove_deck))
+            .command("cah export", |c| c.required_permissions(Permissions::ADMINISTRATOR).exec(cah::commands::export_deck))
             .command("cah set-decks", |c| c.guild_only(true).exec(cah::commands::set_decks))
-            .command("cah pick", |c| c.guild_only(true).exec(cah::commands::pick_winner)))
+            .command("cah answer", |c| c.dm_only(false /*todo*/).exec(cah::commands::answer))
+            .command("cah pick", |c| c.guild_only(true).exec(cah::commands::pick_winner))
+            .command("cah vote", |c| c.dm_only(false /*todo*/).exec(cah::commands::vote))
+            .command("cah status", |c| c.guild_only(true).exec(cah::commands::show_status))
+            .command("cah mode", |c| c.guild_only(true).exec(cah::commands::set_mode))
+            .command("cah judging", |c| c.guild_only(true).exec(cah::commands::set_judging))
+            .on("cah rules", cah::commands::get_rules)
+            .command("cah rule", |c| c.guild_only(true).exec(cah::commands::set_rule))
+            .command("cah set-win", |c| c.guild_only(true).exec(cah::commands::set_win_condition))
+            .command("cah set-timer", |c| c.guild_only(true).exec(cah::commands::set_round_timer))
+            .command("cah set-blanks", |c| c.guild_only(true).exec(cah::commands::set_blank_cards)))
         .after(command_error_handler);
     client.with_framework(framework);
     let mut white_cards = Vec::new();
     let mut black_cards = Vec::new();
-    for deck in config.cah.default_decks.iter() {
-        let (mut black, mut white) = cah::load_deck(deck).expect(&format!("Error loading deck {}", deck));
-        white_cards.append(&mut white);
-        black_cards.append(&mut black);
-    }
+    config.cah.default_decks = match cah::deck::resolve_dependencies(&config.cah.default_decks, None) {
+        Ok(decks) => decks,
+        Err(err) => {
+            eprintln!("Error resolving deck dependencies: {} (run with --check-decks for details)", err);
+            process::exit(1);
+        },
+    };
+    config.cah.default_decks.retain(|deck| match cah::load_deck(deck, None) {
+        Ok((mut black, mut white)) => {
+            white_cards.append(&mut white);
+            black_cards.append(&mut black);
+            true
+        },
+        Err(err) => {
+            eprintln!("Error loading deck {}, skipping it: {} (run with --check-decks for details)", deck, err);
+            false
+        },
+    });
     {
         let mut data = client.data.lock();
         let mut default_decks = Vec::new();
         //MiCrO-oPtImIzAtIoNs ArE UsElEsS
         mem::swap(&mut default_decks, &mut config.cah.default_decks);
-        let cah_manager = CahManager::new(black_cards, white_cards, default_decks);
+        let mut cah_manager = CahManager::new(black_cards, white_cards, default_decks);
+        cah_manager.set_blank_cards(config.cah.blank_cards);
+        cah_manager.set_sfw_policy(config.cah.sfw_channels);
+        cah_manager.set_plain_text(config.cah.plain_text);
+        if config.cah.card_images {
+            match config.cah.card_font.as_ref().map(|x| cah::render::CardRenderer::load(x)) {
+                Some(Ok(renderer)) => cah_manager.set_renderer(Some(renderer)),
+                Some(Err(err)) => eprintln!("Error loading card font, sending cards as text: {}", err),
+                None => eprintln!("card-images is on but there's no card-font, sending cards as text"),
+            }
+        }
         data.insert::<CahManager>(cah_manager);
     }
     client.start().expect("Error occurred starting client")
 }
 
 fn get_name(message: &Message) -> String {
-    message.guild_id.and_then(|g| g.member(message.author.id).ok()).and_then(|m| m.nick.clone()).unwrap_or(message.author.name.clone())
+    // people pick their own names, so they're escaped like card text
+    cah::display::escape(&message.guild_id.and_then(|g| g.member(message.author.id).ok()).and_then(|m| m.nick.clone()).unwrap_or(message.author.name.clone()))
 }
 
 fn get_name_other(message: &Message, id: UserId) -> String {
-    message.guild_id.and_then(|g| g.member(id).ok()).and_then(|m| m.nick.clone()).unwrap_or_else(|| id.to_user().ok().map(|u| u.name.clone()).unwrap_or_else(|| format!("<@{}>", id.0)))
+    match message.guild_id.and_then(|g| g.member(id).ok()).and_then(|m| m.nick.clone()).or_else(|| id.to_user().ok().map(|u| u.name.clone())) {
+        Some(name) => cah::display::escape(&name),
+        None => format!("<@{}>", id.0),
+    }
 }
 
 fn command_error_handler(_c: &mut Context, m: &Message, _name: &str, res: CommandResult) {
     if let Err(err) = res {
-        m.channel_id.say(err.0).ok();
+        // errors often repeat what was typed
+        cah::display::say(m.channel_id, &cah::display::escape_mentions(&err.0)).ok();
     }
 }
 
 struct Handler;
-impl EventHandler for Handler {}
+impl EventHandler for Handler {
+    fn reaction_add(&self, c: Context, r: Reaction) {
+        if let Err(err) = cah::commands::on_reaction(&c, &r) {
+            cah::display::say(r.channel_id, &cah::display::escape_mentions(&err.0)).ok();
+        }
+    }
+    fn reaction_remove(&self, c: Context, r: Reaction) {
+        if let Err(err) = cah::commands::on_reaction_remove(&c, &r) {
+            eprintln!("Error handling a removed reaction: {}", err.0);
+        }
+    }
+}
 
 fn ping(_c: &mut Context, m: &Message, _a: Args) -> CommandResult {
     m.channel_id.say("Pong!")?;
@@ -95,4 +191,16 @@ struct Config {
 #[serde(rename_all = "kebab-case")]
 struct CahSection {
     default_decks: Vec<String>,
+    #[serde(default)]
+    blank_cards: u8,
+    #[serde(default)]
+    sfw_channels: cah::SfwPolicy,
+    // black cards are sent as images as well as text when this is on
+    #[serde(default)]
+    card_images: bool,
+    #[serde(default)]
+    card_font: Option<String>,
+    // send everything as plain messages instead of embeds
+    #[serde(default)]
+    plain_text: bool,
 }